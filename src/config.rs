@@ -1,9 +1,12 @@
+use formatters::{FormatOptions, TemplateSettings};
 use serde_yaml;
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::path::Path;
-use argparse::{ArgumentParser, StoreOption, Collect};
+use std::path::{Path, PathBuf};
+use subnet_tree::TreeOptions;
+use argparse::{ArgumentParser, StoreOption, StoreTrue, Collect};
 use log4rs::config::{Config, Appender, Root};
-use log4rs::append::console::ConsoleAppender;
+use log4rs::append::console::{ConsoleAppender, Target};
 use log::LevelFilter;
 
 const SETTINGS_FILE_NAME: &'static str = "settings.yaml";
@@ -14,14 +17,148 @@ pub struct Receivers {
     receiver: String,
     #[serde(default = "default_udp_receiver")]
     udp_address: Option<String>,
-    kafka: Option<KafkaReceiver>
+    #[serde(default)]
+    tcp_address: Option<String>,
+    #[serde(default = "default_framing")]
+    tcp_framing: String,
+    #[serde(default)]
+    http_address: Option<String>,
+    #[serde(default)]
+    unix: Option<UnixReceiver>,
+    #[serde(default)]
+    tail: Option<TailReceiver>,
+    kafka: Option<KafkaReceiver>,
+    #[serde(default = "default_parser")]
+    parser: String,
+    #[serde(default)]
+    tag: Option<String>,
+}
+
+impl Receivers {
+    fn default() -> Receivers {
+        Receivers {
+            receiver: default_receiver(),
+            udp_address: default_udp_receiver(),
+            tcp_address: None,
+            tcp_framing: default_framing(),
+            http_address: None,
+            unix: None,
+            tail: None,
+            kafka: None,
+            parser: default_parser(),
+            tag: None,
+        }
+    }
+
+    pub fn get_receiver_type(&self) -> &str {
+        self.receiver.as_ref()
+    }
+
+    pub fn get_udp_bind_address(&self) -> Option<String> {
+        self.udp_address.clone()
+    }
+
+    pub fn get_tcp_bind_address(&self) -> Option<String> {
+        self.tcp_address.clone()
+    }
+
+    pub fn get_tcp_framing(&self) -> &str {
+        self.tcp_framing.as_ref()
+    }
+
+    pub fn get_http_bind_address(&self) -> Option<String> {
+        self.http_address.clone()
+    }
+
+    pub fn get_unix_receiver(&self) -> Option<UnixReceiver> {
+        self.unix.clone()
+    }
+
+    pub fn get_tail_receiver(&self) -> Option<TailReceiver> {
+        self.tail.clone()
+    }
+
+    pub fn get_kafka_receiver_credentials(&self) -> Option<KafkaReceiver> {
+        self.kafka.clone()
+    }
+
+    pub fn get_parser(&self) -> &str {
+        self.parser.as_ref()
+    }
+
+    pub fn get_tag(&self) -> Option<String> {
+        self.tag.clone()
+    }
+}
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
+pub struct TailReceiver {
+    paths: Vec<String>,
+    #[serde(default)]
+    state_file: Option<String>,
+    #[serde(default = "default_poll_interval_ms")]
+    poll_interval_ms: u64,
+}
+
+impl TailReceiver {
+    pub fn get_paths(&self) -> Vec<String> {
+        self.paths.clone()
+    }
+
+    pub fn get_state_file(&self) -> Option<String> {
+        self.state_file.clone()
+    }
+
+    pub fn get_poll_interval_ms(&self) -> u64 {
+        self.poll_interval_ms
+    }
+}
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
+pub struct UnixReceiver {
+    path: String,
+    #[serde(default = "default_unix_mode")]
+    mode: String,
+    #[serde(default)]
+    permissions: Option<String>,
+    #[serde(default = "default_framing")]
+    framing: String,
+}
+
+impl UnixReceiver {
+    pub fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    pub fn get_mode(&self) -> &str {
+        self.mode.as_ref()
+    }
+
+    pub fn get_framing(&self) -> &str {
+        self.framing.as_ref()
+    }
+
+    /// Socket file permissions written as an octal string, e.g. `"0660"`.
+    pub fn get_permissions(&self) -> Result<Option<u32>, String> {
+        match self.permissions {
+            Some(ref mode) => match u32::from_str_radix(mode.trim_start_matches("0o"), 8) {
+                Ok(mode) => Ok(Some(mode)),
+                Err(e) => Err(format!("Invalid unix socket permissions `{}`; Cause: {}", mode, e))
+            },
+            None => Ok(None)
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
 pub struct KafkaReceiver {
     hosts: Vec<String>,
     topic: String,
-    group: String
+    group: String,
+    #[serde(default)]
+    partitions: Option<Vec<i32>>,
+    #[serde(default = "default_fallback_offset")]
+    fallback_offset: String,
 }
 
 impl KafkaReceiver {
@@ -42,6 +179,15 @@ impl KafkaReceiver {
     pub fn get_group(&self) -> String {
         self.group.clone()
     }
+
+    /// Partitions to consume; `None` means all partitions discovered from topic metadata.
+    pub fn get_partitions(&self) -> Option<Vec<i32>> {
+        self.partitions.clone()
+    }
+
+    pub fn get_fallback_offset(&self) -> &str {
+        self.fallback_offset.as_ref()
+    }
 }
 
 #[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
@@ -50,7 +196,246 @@ pub struct Senders {
     sender: String,
     #[serde(default = "default_udp_sender")]
     udp_address: Option<String>,
-    kafka: Option<KafkaSender>
+    #[serde(default)]
+    udp_bind_address: Option<String>,
+    #[serde(default)]
+    multicast_ttl: Option<u32>,
+    #[serde(default = "default_max_datagram_size")]
+    max_datagram_size: usize,
+    #[serde(default)]
+    tcp: Option<TcpSender>,
+    #[serde(default)]
+    file: Option<FileSender>,
+    #[serde(default)]
+    webhook: Option<WebhookSender>,
+    kafka: Option<KafkaSender>,
+    #[serde(default = "default_formatter")]
+    formatter: String,
+    #[serde(default = "default_list_name")]
+    list_name: String,
+    #[serde(default)]
+    swap: bool,
+    #[serde(default = "default_nft_table")]
+    nft_table: String,
+    #[serde(default = "default_seq_start")]
+    seq_start: u64,
+    #[serde(default = "default_seq_step")]
+    seq_step: u64,
+    #[serde(default)]
+    template: Option<TemplateSettings>,
+    #[serde(default = "default_sink_queue_size")]
+    queue_size: usize,
+}
+
+impl Senders {
+    fn default() -> Senders {
+        Senders {
+            sender: default_sender(),
+            udp_address: default_udp_sender(),
+            udp_bind_address: None,
+            multicast_ttl: None,
+            max_datagram_size: default_max_datagram_size(),
+            tcp: None,
+            file: None,
+            webhook: None,
+            kafka: None,
+            formatter: default_formatter(),
+            list_name: default_list_name(),
+            swap: false,
+            nft_table: default_nft_table(),
+            seq_start: default_seq_start(),
+            seq_step: default_seq_step(),
+            template: None,
+            queue_size: default_sink_queue_size(),
+        }
+    }
+
+    pub fn get_publisher_type(&self) -> &str {
+        self.sender.as_ref()
+    }
+
+    pub fn get_udp_send_to(&self) -> Option<String> {
+        self.udp_address.clone()
+    }
+
+    pub fn get_udp_bind_address(&self) -> Option<String> {
+        self.udp_bind_address.clone()
+    }
+
+    pub fn get_multicast_ttl(&self) -> Option<u32> {
+        self.multicast_ttl
+    }
+
+    pub fn get_max_datagram_size(&self) -> usize {
+        self.max_datagram_size
+    }
+
+    pub fn get_tcp_publisher_settings(&self) -> Option<TcpSender> {
+        self.tcp.clone()
+    }
+
+    pub fn get_file_publisher_settings(&self) -> Option<FileSender> {
+        self.file.clone()
+    }
+
+    pub fn get_webhook_publisher_settings(&self) -> Option<WebhookSender> {
+        self.webhook.clone()
+    }
+
+    pub fn get_kafka_publisher_credentials(&self) -> Option<KafkaSender> {
+        self.kafka.clone()
+    }
+
+    pub fn get_formatter(&self) -> &str {
+        self.formatter.as_ref()
+    }
+
+    pub fn get_list_name(&self) -> String {
+        self.list_name.clone()
+    }
+
+    pub fn is_swap(&self) -> bool {
+        self.swap
+    }
+
+    pub fn get_nft_table(&self) -> String {
+        self.nft_table.clone()
+    }
+
+    pub fn get_seq_start(&self) -> u64 {
+        self.seq_start
+    }
+
+    pub fn get_seq_step(&self) -> u64 {
+        self.seq_step
+    }
+
+    pub fn get_format_options(&self) -> FormatOptions {
+        FormatOptions {
+            max_size: self.get_max_datagram_size(),
+            list_name: self.get_list_name(),
+            swap: self.is_swap(),
+            nft_table: self.get_nft_table(),
+            seq_start: self.get_seq_start(),
+            seq_step: self.get_seq_step(),
+            template: self.template.clone(),
+        }
+    }
+
+    pub fn get_queue_size(&self) -> usize {
+        self.queue_size
+    }
+}
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
+pub struct TcpSender {
+    address: String,
+    #[serde(default = "default_framing")]
+    framing: String,
+    #[serde(default = "default_tcp_buffer_dumps")]
+    buffer_dumps: usize,
+    #[serde(default = "default_reconnect_min_ms")]
+    reconnect_min_ms: u64,
+    #[serde(default = "default_reconnect_max_ms")]
+    reconnect_max_ms: u64,
+}
+
+impl TcpSender {
+    pub fn get_address(&self) -> String {
+        self.address.clone()
+    }
+
+    pub fn get_framing(&self) -> &str {
+        self.framing.as_ref()
+    }
+
+    pub fn get_buffer_dumps(&self) -> usize {
+        self.buffer_dumps
+    }
+
+    pub fn get_reconnect_min_ms(&self) -> u64 {
+        self.reconnect_min_ms
+    }
+
+    pub fn get_reconnect_max_ms(&self) -> u64 {
+        self.reconnect_max_ms
+    }
+}
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
+pub struct FileSender {
+    path: String,
+    #[serde(default = "default_keep_files")]
+    keep: usize,
+    #[serde(default)]
+    compress: bool,
+}
+
+impl FileSender {
+    pub fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    pub fn get_keep(&self) -> usize {
+        self.keep
+    }
+
+    pub fn is_compress(&self) -> bool {
+        self.compress
+    }
+}
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookSender {
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default = "default_webhook_body")]
+    body: String,
+    #[serde(default = "default_webhook_mode")]
+    mode: String,
+    #[serde(default = "default_webhook_retries")]
+    retries: u32,
+    #[serde(default = "default_retry_backoff_ms")]
+    retry_backoff_ms: u64,
+    #[serde(default = "default_webhook_timeout_ms")]
+    timeout_ms: u64,
+    #[serde(default)]
+    dead_letter: Option<String>,
+}
+
+impl WebhookSender {
+    pub fn get_url(&self) -> &str {
+        self.url.as_ref()
+    }
+
+    pub fn get_headers(&self) -> Vec<(String, String)> {
+        self.headers.iter().map(|(name, value)| (name.clone(), value.clone())).collect()
+    }
+
+    pub fn get_body(&self) -> &str {
+        self.body.as_ref()
+    }
+
+    pub fn get_mode(&self) -> &str {
+        self.mode.as_ref()
+    }
+
+    pub fn get_retries(&self) -> u32 {
+        self.retries
+    }
+
+    pub fn get_retry_backoff_ms(&self) -> u64 {
+        self.retry_backoff_ms
+    }
+
+    pub fn get_timeout_ms(&self) -> u64 {
+        self.timeout_ms
+    }
+
+    pub fn get_dead_letter(&self) -> Option<String> {
+        self.dead_letter.clone()
+    }
 }
 
 #[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
@@ -58,7 +443,15 @@ pub struct KafkaSender {
     hosts: Vec<String>,
     topic: String,
     #[serde(default = "default_ack_duration")]
-    ack_duration_seconds: u64
+    ack_duration_seconds: u64,
+    #[serde(default = "default_required_acks")]
+    required_acks: String,
+    #[serde(default = "default_compression")]
+    compression: String,
+    #[serde(default = "default_message_key")]
+    message_key: String,
+    #[serde(default)]
+    batch: bool,
 }
 
 impl KafkaSender {
@@ -77,14 +470,40 @@ impl KafkaSender {
     pub fn get_ack_duration_seconds(&self) -> u64 {
         self.ack_duration_seconds
     }
+
+    pub fn get_required_acks(&self) -> &str {
+        self.required_acks.as_ref()
+    }
+
+    pub fn get_compression(&self) -> &str {
+        self.compression.as_ref()
+    }
+
+    pub fn get_message_key(&self) -> &str {
+        self.message_key.as_ref()
+    }
+
+    pub fn is_batch(&self) -> bool {
+        self.batch
+    }
 }
 
 #[derive(Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct Settings {
+    #[serde(default = "Receivers::default")]
     receiver: Receivers,
+    #[serde(default)]
+    receivers: Vec<Receivers>,
+    #[serde(default = "Senders::default")]
     sender: Senders,
+    #[serde(default)]
+    senders: Vec<Senders>,
     #[serde(default = "thirty_seconds")]
     publish_timer: u32,
+    #[serde(default = "default_event_queue_size")]
+    event_queue_size: usize,
+    #[serde(default = "default_shutdown_timeout")]
+    shutdown_timeout: u32,
 
     #[serde(default = "default_add_broadcast")]
     auto_add_broadcast: bool,
@@ -92,73 +511,107 @@ pub struct Settings {
     auto_add_zeroed: bool,
     #[serde(default = "default_logger_settings_path")]
     log4rs_settings: Option<String>,
+    #[serde(skip)]
+    oneshot_inputs: Option<Vec<String>>,
+    #[serde(skip)]
+    source_path: Option<PathBuf>,
 }
 
 impl Settings {
     pub fn default() -> Settings {
         Settings {
-            receiver: Receivers {
-                receiver: default_receiver(),
-                udp_address: default_udp_receiver(),
-                kafka: None
-            },
-            sender: Senders {
-                sender: default_sender(),
-                udp_address: default_udp_sender(),
-                kafka: None
-            },
+            receiver: Receivers::default(),
+            receivers: Vec::new(),
+            sender: Senders::default(),
+            senders: Vec::new(),
             publish_timer: thirty_seconds(),
+            event_queue_size: default_event_queue_size(),
+            shutdown_timeout: default_shutdown_timeout(),
             auto_add_zeroed: default_add_zeroed(),
             auto_add_broadcast: default_add_broadcast(),
-            log4rs_settings: default_logger_settings_path()
+            log4rs_settings: default_logger_settings_path(),
+            oneshot_inputs: None,
+            source_path: None,
         }
     }
 
-    pub fn is_last_node_with_settings(&self) -> bool {
-        self.auto_add_broadcast || self.auto_add_zeroed
-    }
-
-    pub fn add_zeroed(&self) -> bool {
-        self.auto_add_zeroed
+    pub fn get_tree_options(&self) -> TreeOptions {
+        TreeOptions {
+            add_zeroed: self.auto_add_zeroed,
+            add_broadcast: self.auto_add_broadcast,
+        }
     }
 
-    pub fn add_broadcast(&self) -> bool {
-        self.auto_add_broadcast
+    pub fn get_publish_timer(&self) -> u32 {
+        self.publish_timer
     }
 
-    pub fn get_udp_bind_address(&self) -> Option<String> {
-        self.receiver.udp_address.clone()
+    pub fn get_event_queue_size(&self) -> usize {
+        self.event_queue_size
     }
 
-    pub fn get_udp_send_to(&self) -> Option<String> {
-        self.sender.udp_address.clone()
-    }
-
-    pub fn get_publish_timer(&self) -> u32 {
-        self.publish_timer
+    pub fn get_shutdown_timeout(&self) -> u32 {
+        self.shutdown_timeout
     }
 
     pub fn get_logger_config(&self) -> Option<String> {
         self.log4rs_settings.clone()
     }
 
-    pub fn get_receiver_type(&self) -> &str {
-        self.receiver.receiver.as_ref()
+    pub fn get_oneshot_inputs(&self) -> Option<Vec<String>> {
+        self.oneshot_inputs.clone()
     }
 
-    pub fn get_publisher_type(&self) -> &str {
-        self.sender.sender.as_ref()
+    /// Listeners to start; `receivers` list takes precedence over single `receiver` section.
+    pub fn get_receivers(&self) -> Vec<Receivers> {
+        if self.receivers.is_empty() {
+            vec![self.receiver.clone()]
+        } else {
+            self.receivers.clone()
+        }
     }
 
-    pub fn get_kafka_receiver_credentials(&self) -> Option<KafkaReceiver> {
-        self.receiver.kafka.clone()
+    /// Publishers to start; `senders` list takes precedence over single `sender` section.
+    pub fn get_senders(&self) -> Vec<Senders> {
+        if self.senders.is_empty() {
+            vec![self.sender.clone()]
+        } else {
+            self.senders.clone()
+        }
     }
 
-    pub fn get_kafka_publisher_credentials(&self) -> Option<KafkaSender> {
-        self.sender.kafka.clone()
+    /// Names of settings which differ from `other` and are only applied at start.
+    pub fn restart_required_changes(&self, other: &Settings) -> Vec<&'static str> {
+        let mut changes = Vec::new();
+        if self.get_receivers() != other.get_receivers() {
+            changes.push("receivers");
+        }
+        if self.event_queue_size != other.event_queue_size {
+            changes.push("event_queue_size");
+        }
+        if self.auto_add_zeroed != other.auto_add_zeroed {
+            changes.push("auto_add_zeroed");
+        }
+        if self.auto_add_broadcast != other.auto_add_broadcast {
+            changes.push("auto_add_broadcast");
+        }
+        if self.log4rs_settings != other.log4rs_settings {
+            changes.push("log4rs_settings");
+        }
+        changes
     }
 
-    pub fn override_settings(mut self, settings: OverrideSettings) -> Settings {
+    /// Applies command line options to the single `receiver` and `sender` sections. Fails when
+    /// they would be ignored because `receivers` or `senders` list is configured.
+    pub fn override_settings(mut self, settings: OverrideSettings) -> Result<Settings, String> {
+        if !self.receivers.is_empty() && settings.overrides_receiver() {
+            return Err("Command line receiver options can not be combined with `receivers` list; \
+                        Set them in the list entries instead".to_owned());
+        }
+        if !self.senders.is_empty() && settings.overrides_sender() {
+            return Err("Command line sender options can not be combined with `senders` list; \
+                        Set them in the list entries instead".to_owned());
+        }
         if let Some(ref kafka_hosts) = settings.get_kafka_hosts() {
             match self.sender.kafka {
                 Some(ref mut kafka) => {
@@ -169,6 +622,10 @@ impl Settings {
                         hosts: kafka_hosts.iter().map(|host| {host.to_owned()}).collect(),
                         topic: String::new(),
                         ack_duration_seconds: default_ack_duration(),
+                        required_acks: default_required_acks(),
+                        compression: default_compression(),
+                        message_key: default_message_key(),
+                        batch: false,
                     })
                 }
             }
@@ -181,6 +638,8 @@ impl Settings {
                         hosts: kafka_hosts.iter().map(|host| {host.to_owned()}).collect(),
                         topic: String::new(),
                         group: String::new(),
+                        partitions: None,
+                        fallback_offset: default_fallback_offset(),
                     });
                 }
             }
@@ -195,6 +654,8 @@ impl Settings {
                         hosts: Vec::new(),
                         topic: kafka_inbound_topic.to_owned(),
                         group: String::new(),
+                        partitions: None,
+                        fallback_offset: default_fallback_offset(),
                     });
                 }
             }
@@ -207,6 +668,10 @@ impl Settings {
                         hosts: Vec::new(),
                         topic: kafka_outbound_topic.to_owned(),
                         ack_duration_seconds: default_ack_duration(),
+                        required_acks: default_required_acks(),
+                        compression: default_compression(),
+                        message_key: default_message_key(),
+                        batch: false,
                     });
                 }
             }
@@ -219,6 +684,8 @@ impl Settings {
                         hosts: Vec::new(),
                         topic: String::new(),
                         group: kafka_group.to_owned(),
+                        partitions: None,
+                        fallback_offset: default_fallback_offset(),
                     });
                 }
             }
@@ -229,16 +696,22 @@ impl Settings {
         if let Some(sender_type) = settings.get_sender_type() {
             self.sender.sender = sender_type;
         }
+        if settings.is_oneshot() {
+            self.oneshot_inputs = Some(settings.get_inputs());
+        }
         if let Some(receiver_type) = settings.get_receiver_type() {
             self.receiver.receiver = receiver_type;
         }
         if let Some(udp_recv_host) = settings.get_udp_recv_host() {
             self.receiver.udp_address = Some(udp_recv_host);
         }
+        if let Some(tcp_recv_host) = settings.get_tcp_recv_host() {
+            self.receiver.tcp_address = Some(tcp_recv_host);
+        }
         if let Some(udp_send_to_host) = settings.get_udp_send_to_host() {
             self.sender.udp_address = Some(udp_send_to_host);
         }
-        self
+        Ok(self)
     }
 }
 
@@ -259,12 +732,20 @@ pub fn load_from_file(path: &Path) -> Result<Settings, String> {
         Ok(file) => {
             match serde_yaml::from_reader(file) {
                 Err(reason) => Err(format!("Parsing settings file `{}` failed: {}", path.display(), reason)),
-                Ok(settings) => Ok(settings)
+                Ok(settings) => Ok(Settings { source_path: Some(path.to_path_buf()), ..settings })
             }
         }
     }
 }
 
+/// Re-reads the file `current` settings were loaded from and applies command line overrides again.
+pub fn reload_settings(current: &Settings) -> Result<Settings, String> {
+    match current.source_path {
+        Some(ref path) => load_from_file(path)?.override_settings(read_cmd_line_args()),
+        None => Err("Settings were not loaded from a file, there is nothing to reload".to_owned())
+    }
+}
+
 fn default_add_broadcast() -> bool {
     true
 }
@@ -285,18 +766,122 @@ fn thirty_seconds() -> u32 {
     30
 }
 
+fn default_event_queue_size() -> usize {
+    10_000
+}
+
+fn default_shutdown_timeout() -> u32 {
+    10
+}
+
+fn default_framing() -> String {
+    "newline".to_owned()
+}
+
+fn default_unix_mode() -> String {
+    "stream".to_owned()
+}
+
+fn default_poll_interval_ms() -> u64 {
+    1000_u64
+}
+
 fn default_sender() -> String {
     "udp".to_owned()
 }
 
+fn default_formatter() -> String {
+    "simple".to_owned()
+}
+
+fn default_max_datagram_size() -> usize {
+    508
+}
+
+fn default_list_name() -> String {
+    "ipaggregator".to_owned()
+}
+
+fn default_nft_table() -> String {
+    "inet filter".to_owned()
+}
+
+fn default_seq_start() -> u64 {
+    5_u64
+}
+
+fn default_seq_step() -> u64 {
+    5_u64
+}
+
+fn default_sink_queue_size() -> usize {
+    64
+}
+
+fn default_tcp_buffer_dumps() -> usize {
+    16
+}
+
+fn default_reconnect_min_ms() -> u64 {
+    100_u64
+}
+
+fn default_reconnect_max_ms() -> u64 {
+    30_000_u64
+}
+
+fn default_keep_files() -> usize {
+    5
+}
+
+fn default_webhook_body() -> String {
+    "json".to_owned()
+}
+
+fn default_webhook_mode() -> String {
+    "full".to_owned()
+}
+
+fn default_webhook_retries() -> u32 {
+    3
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    500_u64
+}
+
+fn default_webhook_timeout_ms() -> u64 {
+    10_000_u64
+}
+
 fn default_receiver() -> String {
     "udp".to_owned()
 }
 
+fn default_parser() -> String {
+    "nom".to_owned()
+}
+
+fn default_fallback_offset() -> String {
+    "latest".to_owned()
+}
+
 fn default_ack_duration() -> u64 {
     1_u64
 }
 
+fn default_required_acks() -> String {
+    "one".to_owned()
+}
+
+fn default_compression() -> String {
+    "none".to_owned()
+}
+
+fn default_message_key() -> String {
+    "none".to_owned()
+}
+
 fn default_logger_settings_path() -> Option<String> {
     None
 }
@@ -309,9 +894,12 @@ pub struct OverrideSettings {
     kafka_outbound_topic: Option<String>,
     kafka_group: Option<String>,
     udp_recv_host: Option<String>,
+    tcp_recv_host: Option<String>,
     udp_send_to_host: Option<String>,
     receiver: Option<String>,
-    sender: Option<String>
+    sender: Option<String>,
+    oneshot: bool,
+    inputs: Vec<String>,
 }
 
 impl OverrideSettings {
@@ -324,9 +912,12 @@ impl OverrideSettings {
             kafka_outbound_topic: None,
             kafka_group: None,
             udp_recv_host: None,
+            tcp_recv_host: None,
             udp_send_to_host: None,
             receiver: None,
             sender: None,
+            oneshot: false,
+            inputs: Vec::new(),
         }
     }
 
@@ -358,6 +949,10 @@ impl OverrideSettings {
         self.udp_recv_host.clone()
     }
 
+    pub fn get_tcp_recv_host(&self) -> Option<String> {
+        self.tcp_recv_host.clone()
+    }
+
     pub fn get_udp_send_to_host(&self) -> Option<String> {
         self.udp_send_to_host.clone()
     }
@@ -373,6 +968,24 @@ impl OverrideSettings {
     pub fn get_kafka_group(&self) -> Option<String> {
         self.kafka_group.clone()
     }
+
+    pub fn is_oneshot(&self) -> bool {
+        self.oneshot
+    }
+
+    pub fn get_inputs(&self) -> Vec<String> {
+        self.inputs.clone()
+    }
+
+    pub fn overrides_receiver(&self) -> bool {
+        !self.kafka_hosts.is_empty() || self.kafka_inbound_topic.is_some() || self.kafka_group.is_some()
+            || self.receiver.is_some() || self.udp_recv_host.is_some() || self.tcp_recv_host.is_some()
+    }
+
+    pub fn overrides_sender(&self) -> bool {
+        !self.kafka_hosts.is_empty() || self.kafka_outbound_topic.is_some() || self.sender.is_some()
+            || self.udp_send_to_host.is_some()
+    }
 }
 
 pub fn read_cmd_line_args() -> OverrideSettings {
@@ -381,14 +994,17 @@ pub fn read_cmd_line_args() -> OverrideSettings {
         let mut ap: ArgumentParser = ArgumentParser::new();
         ap.set_description("Small uService for IPv4 Addresses aggregation to ip ranges in CIDR format.");
         ap.refer(&mut cmd_settings.settings_path).add_option(&["-c", "--config-path"], StoreOption, "Alternative config file path.");
-        ap.refer(&mut cmd_settings.receiver).add_option(&["-r", "--receiver"], StoreOption, "Receiver type. Defaults to `udp`. Possible options are [`udp`, `kafka`].");
-        ap.refer(&mut cmd_settings.sender).add_option(&["-s", "--sender"], StoreOption, "Sender type. Defaults to `udp`. Possible options are [`udp`, `kafka`]");
+        ap.refer(&mut cmd_settings.receiver).add_option(&["-r", "--receiver"], StoreOption, "Receiver type. Defaults to `udp`. Possible options are [`udp`, `tcp`, `http`, `unix`, `tail`, `kafka`].");
+        ap.refer(&mut cmd_settings.sender).add_option(&["-s", "--sender"], StoreOption, "Sender type. Defaults to `udp`. Possible options are [`udp`, `tcp`, `file`, `webhook`, `kafka`]");
         ap.refer(&mut cmd_settings.kafka_hosts).add_option(&["--kafka-hosts"], Collect, "Kafka hosts, if kafka option is specified.");
         ap.refer(&mut cmd_settings.kafka_inbound_topic).add_option(&["--kafka-inbound-topic"], StoreOption, "Kafka consumer topic.");
         ap.refer(&mut cmd_settings.kafka_outbound_topic).add_option(&["--kafka-outbound-topic"], StoreOption, "Kafka send_to topic");
         ap.refer(&mut cmd_settings.kafka_group).add_option(&["--kafka-receiver-group"], StoreOption, "Kafka group.");
         ap.refer(&mut cmd_settings.udp_recv_host).add_option(&["--udp-receiver-host"], StoreOption, "Udp receiver host.");
+        ap.refer(&mut cmd_settings.tcp_recv_host).add_option(&["--tcp-receiver-host"], StoreOption, "Tcp receiver host.");
         ap.refer(&mut cmd_settings.udp_send_to_host).add_option(&["--udp-send-to-host"], StoreOption, "Udp send to host.");
+        ap.refer(&mut cmd_settings.oneshot).add_option(&["--oneshot"], StoreTrue, "Aggregate inputs once, print CIDRs to stdout and exit.");
+        ap.refer(&mut cmd_settings.inputs).add_option(&["-i", "--input"], Collect, "One-shot input file, `-` for stdin. Gzip is detected. Defaults to stdin.");
         ap.refer(&mut cmd_settings.logger_config).add_option(&["-l", "--log4rs-config"], StoreOption, "log4rs configuration file path");
         ap.parse_args_or_exit();
     };
@@ -396,11 +1012,13 @@ pub fn read_cmd_line_args() -> OverrideSettings {
 }
 
 
-pub fn default_log4rs_config() -> Config {
-    let stdout = ConsoleAppender::builder().build();
+/// Logs to stdout, or to stderr in one-shot mode where stdout is reserved for aggregated output.
+pub fn default_log4rs_config(oneshot: bool) -> Config {
+    let target = if oneshot { Target::Stderr } else { Target::Stdout };
+    let console = ConsoleAppender::builder().target(target).build();
 
     let config = Config::builder()
-        .appender(Appender::builder().build("stdout", Box::new(stdout)))
-        .build(Root::builder().appender("stdout").build(LevelFilter::Info)).unwrap();
+        .appender(Appender::builder().build("console", Box::new(console)))
+        .build(Root::builder().appender("console").build(LevelFilter::Info)).unwrap();
     config
 }
\ No newline at end of file
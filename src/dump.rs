@@ -0,0 +1,32 @@
+use std::fmt;
+
+/// Describes one publish cycle; sent with both markers framing its chunks.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct DumpInfo {
+    /// Increases by one with every dump, starting at 1.
+    pub sequence: u64,
+    /// Seconds since UNIX epoch when the previous dump was taken, or the aggregator started.
+    pub window_start: u64,
+    /// Seconds since UNIX epoch when the dump was taken.
+    pub timestamp: u64,
+    /// Number of prefixes in the whole dump.
+    pub total: usize,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum DumpEvent {
+    Begin(DumpInfo),
+    /// Offset of the first prefix within the dump and the prefixes.
+    Chunk(usize, Vec<(u32, u8)>),
+    End(DumpInfo),
+}
+
+impl fmt::Display for DumpEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DumpEvent::Begin(ref info) => write!(f, "begin marker of dump {}", info.sequence),
+            DumpEvent::Chunk(offset, ref cidrs) => write!(f, "{} prefixes at offset {}", cidrs.len(), offset),
+            DumpEvent::End(ref info) => write!(f, "end marker of dump {}", info.sequence),
+        }
+    }
+}
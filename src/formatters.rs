@@ -1,43 +1,784 @@
-pub type AggFormatter = fn(Vec<(u32, u8)>) -> Vec<String>;
-const MAX_UDP_DATAGRAM_PAYLOAD_SIZE: usize = 508;
+use dump::{DumpEvent, DumpInfo};
+use serde::Serialize;
+use serde_json;
+use std::cmp;
+use std::mem;
 
-pub fn simple_formatter(cidrs: Vec<(u32, u8)>) -> Vec<String> {
-    let mut from: usize = 0;
-    let mut concated_msg: Vec<String> = Vec::new();
-    while from < cidrs.len() - 1 {
-        let (msg, idx) = concat_to_size(&cidrs[from..], MAX_UDP_DATAGRAM_PAYLOAD_SIZE);
-        from += idx;
-        concated_msg.push(msg);
+pub const MAX_UDP_DATAGRAM_PAYLOAD_SIZE: usize = 508;
+
+/// Per sink settings formatters take into account.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct FormatOptions {
+    /// Upper bound of a single message in bytes.
+    pub max_size: usize,
+    /// Name of the firewall set the dump is loaded into.
+    pub list_name: String,
+    /// Replace the set atomically instead of adding to it.
+    pub swap: bool,
+    /// Family and name of nftables table holding the set.
+    pub nft_table: String,
+    /// Sequence number of the first router list entry.
+    pub seq_start: u64,
+    /// Increment of sequence numbers between router list entries.
+    pub seq_step: u64,
+    /// Settings of the template formatter.
+    pub template: Option<TemplateSettings>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions {
+            max_size: MAX_UDP_DATAGRAM_PAYLOAD_SIZE,
+            list_name: "ipaggregator".to_owned(),
+            swap: false,
+            nft_table: "inet filter".to_owned(),
+            seq_start: 5,
+            seq_step: 5,
+            template: None,
+        }
+    }
+}
+
+/// Templates of the template formatter, see `TemplateFormatter`.
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
+pub struct TemplateSettings {
+    /// Rendered for every prefix.
+    pub line: String,
+    /// Rendered at the beginning of the dump.
+    #[serde(default)]
+    pub header: String,
+    /// Rendered at the end of the dump.
+    #[serde(default)]
+    pub footer: String,
+    /// Put between rendered lines.
+    #[serde(default = "default_template_separator")]
+    pub separator: String,
+    /// Escaping of substituted values, `none`, `json`, `shell` or `xml`.
+    #[serde(default = "default_template_escape")]
+    pub escape: String,
+}
+
+/// Turns dump events of one sink into messages. Formatter lives as long as its sink, so it may
+/// keep state across the chunks of a dump.
+pub trait Formatter {
+    fn begin(&mut self, info: &DumpInfo) -> Vec<Vec<u8>>;
+
+    /// Called with prefixes of the dump starting at `offset`.
+    fn chunk(&mut self, offset: usize, cidrs: &[(u32, u8)]) -> Vec<Vec<u8>>;
+
+    fn end(&mut self, info: &DumpInfo) -> Vec<Vec<u8>>;
+
+    /// Whether every message fits `max_size` of the options.
+    fn bounded(&self) -> bool {
+        true
+    }
+
+    /// Whether messages are binary, so they can not be delimited by newlines.
+    fn binary(&self) -> bool {
+        false
+    }
+
+    /// Whether every message fits on one line, as required by newline framing.
+    fn single_line(&self) -> bool {
+        false
+    }
+
+    /// Whether output of a chunk depends on previous chunks of the dump.
+    fn stateful(&self) -> bool {
+        false
+    }
+
+    fn format(&mut self, event: &DumpEvent) -> Vec<Vec<u8>> {
+        match *event {
+            DumpEvent::Begin(ref info) => self.begin(info),
+            DumpEvent::Chunk(offset, ref cidrs) => self.chunk(offset, cidrs),
+            DumpEvent::End(ref info) => self.end(info),
+        }
+    }
+}
+
+/// Stateless text formatter function; used through `TextFormatter`.
+pub type AggFormatter = fn(&DumpEvent, &FormatOptions) -> Vec<String>;
+
+pub struct TextFormatter {
+    formatter: AggFormatter,
+    options: FormatOptions,
+    single_line: bool,
+}
+
+impl TextFormatter {
+    pub fn new(formatter: AggFormatter, options: FormatOptions) -> TextFormatter {
+        TextFormatter { formatter, options, single_line: false }
+    }
+
+    /// Marks the formatter as producing single line messages only.
+    pub fn with_single_line_messages(mut self) -> TextFormatter {
+        self.single_line = true;
+        self
+    }
+}
+
+impl Formatter for TextFormatter {
+    fn begin(&mut self, info: &DumpInfo) -> Vec<Vec<u8>> {
+        self.format(&DumpEvent::Begin(*info))
+    }
+
+    fn chunk(&mut self, offset: usize, cidrs: &[(u32, u8)]) -> Vec<Vec<u8>> {
+        self.format(&DumpEvent::Chunk(offset, cidrs.to_vec()))
+    }
+
+    fn end(&mut self, info: &DumpInfo) -> Vec<Vec<u8>> {
+        self.format(&DumpEvent::End(*info))
+    }
+
+    fn single_line(&self) -> bool {
+        self.single_line
+    }
+
+    fn format(&mut self, event: &DumpEvent) -> Vec<Vec<u8>> {
+        (self.formatter)(event, &self.options).into_iter().map(String::into_bytes).collect()
+    }
+}
+
+pub fn formatter_by_name(name: &str, options: FormatOptions) -> Result<Box<Formatter>, String> {
+    let formatter: AggFormatter = match name {
+        "json" => return Ok(Box::new(JsonFormatter::new())),
+        "range" => return Ok(Box::new(RangeFormatter::new(options))),
+        "binary" => return Ok(Box::new(BinaryFormatter::new(options))),
+        "template" => return Ok(Box::new(TemplateFormatter::new(options)?)),
+        "simple" => return Ok(Box::new(TextFormatter::new(simple_formatter, options).with_single_line_messages())),
+        "ipset" => ipset_formatter,
+        "nft" => nft_formatter,
+        "cisco_prefix_list" => cisco_prefix_list_formatter,
+        "cisco_acl" => cisco_acl_formatter,
+        "juniper" => juniper_formatter,
+        "bird" => bird_formatter,
+        "ndjson" => ndjson_formatter,
+        "cidr_range" => cidr_range_formatter,
+        other => return Err(format!("Unknown formatter `{}` specified!", other))
+    };
+    Ok(Box::new(TextFormatter::new(formatter, options)))
+}
+
+/// Space separated CIDRs packed into UDP sized messages. Every dump is framed by
+/// `BEGIN <sequence> <timestamp> <total>` and `END <sequence> <timestamp> <total>` messages.
+pub fn simple_formatter(event: &DumpEvent, options: &FormatOptions) -> Vec<String> {
+    match *event {
+        DumpEvent::Begin(ref info) => vec![format_marker("BEGIN", info)],
+        DumpEvent::Chunk(_, ref cidrs) => pack(cidrs.iter().map(make_cidr_ip_string).collect(), " ", options.max_size),
+        DumpEvent::End(ref info) => vec![format_marker("END", info)],
+    }
+}
+
+fn format_marker(kind: &str, info: &DumpInfo) -> String {
+    format!("{} {} {} {}", kind, info.sequence, info.timestamp, info.total)
+}
+
+/// Joins `items` with `separator` into messages of at most `max_size` bytes. An item longer
+/// than `max_size` is sent as a message of its own.
+pub fn pack(items: Vec<String>, separator: &str, max_size: usize) -> Vec<String> {
+    let mut messages: Vec<String> = Vec::new();
+    let mut message = String::new();
+    for item in items {
+        if !message.is_empty() && message.len() + separator.len() + item.len() > max_size {
+            messages.push(message);
+            message = String::new();
+        }
+        if !message.is_empty() {
+            message.push_str(separator);
+        }
+        message.push_str(&item);
+    }
+    if !message.is_empty() {
+        messages.push(message);
+    }
+    messages
+}
+
+/// `ipset restore` script of `hash:net` set. Addresses are added to the existing set, or
+/// with `swap` loaded into a temporary set which then atomically replaces the set.
+pub fn ipset_formatter(event: &DumpEvent, options: &FormatOptions) -> Vec<String> {
+    let name = &options.list_name;
+    let target = if options.swap { format!("{}-swap", name) } else { name.clone() };
+    let lines = match *event {
+        DumpEvent::Begin(_) => {
+            let mut lines = vec![format!("create {} hash:net family inet -exist", name)];
+            if options.swap {
+                lines.push(format!("create {} hash:net family inet -exist", target));
+                lines.push(format!("flush {}", target));
+            }
+            lines
+        }
+        DumpEvent::Chunk(_, ref cidrs) => {
+            cidrs.iter().map(|cidr| format!("add {} {} -exist", target, make_cidr_ip_string(cidr))).collect()
+        }
+        DumpEvent::End(_) => {
+            if options.swap {
+                vec![format!("swap {} {}", target, name), format!("destroy {}", target)]
+            } else {
+                Vec::new()
+            }
+        }
+    };
+    pack(lines, "\n", options.max_size)
+}
+
+/// `nft -f` script of interval set. Addresses are added to the existing set, or with `swap` the
+/// set is flushed first; `nft -f` applies the whole script as a single transaction.
+pub fn nft_formatter(event: &DumpEvent, options: &FormatOptions) -> Vec<String> {
+    let set = format!("{} {}", options.nft_table, options.list_name);
+    match *event {
+        DumpEvent::Begin(_) => {
+            let mut lines = vec![format!("add set {} {{ type ipv4_addr; flags interval; }}", set)];
+            if options.swap {
+                lines.push(format!("flush set {}", set));
+            }
+            pack(lines, "\n", options.max_size)
+        }
+        DumpEvent::Chunk(_, ref cidrs) => {
+            let head = format!("add element {} {{ ", set);
+            let budget = options.max_size.saturating_sub(head.len() + 2);
+            let elements = cidrs.iter().map(make_cidr_ip_string).collect();
+            pack(elements, ", ", budget).into_iter().map(|elements| format!("{}{} }}", head, elements)).collect()
+        }
+        DumpEvent::End(_) => Vec::new()
+    }
+}
+
+/// Sequence number of prefix at `index` within the dump.
+fn sequence_number(options: &FormatOptions, index: usize) -> u64 {
+    options.seq_start + index as u64 * options.seq_step
+}
+
+/// Cisco IOS (and FRR) `ip prefix-list`; with `swap` the list is removed first.
+pub fn cisco_prefix_list_formatter(event: &DumpEvent, options: &FormatOptions) -> Vec<String> {
+    let name = &options.list_name;
+    let lines = match *event {
+        DumpEvent::Begin(_) if options.swap => vec![format!("no ip prefix-list {}", name)],
+        DumpEvent::Chunk(offset, ref cidrs) => cidrs.iter().enumerate().map(|(idx, cidr)| {
+            format!("ip prefix-list {} seq {} permit {}", name, sequence_number(options, offset + idx), make_cidr_ip_string(cidr))
+        }).collect(),
+        _ => Vec::new()
+    };
+    pack(lines, "\n", options.max_size)
+}
+
+/// Cisco IOS standard named ACL using wildcard masks; with `swap` the list is removed first.
+pub fn cisco_acl_formatter(event: &DumpEvent, options: &FormatOptions) -> Vec<String> {
+    let name = &options.list_name;
+    let lines = match *event {
+        DumpEvent::Begin(_) => {
+            let mut lines = Vec::new();
+            if options.swap {
+                lines.push(format!("no ip access-list standard {}", name));
+            }
+            lines.push(format!("ip access-list standard {}", name));
+            lines
+        }
+        DumpEvent::Chunk(offset, ref cidrs) => cidrs.iter().enumerate().map(|(idx, &(network, len))| {
+            let wildcard = if len >= 32 { 0 } else { u32::max_value() >> len };
+            format!(" {} permit {} {}", sequence_number(options, offset + idx), make_ip_string(network), make_ip_string(wildcard))
+        }).collect(),
+        DumpEvent::End(_) => Vec::new()
+    };
+    pack(lines, "\n", options.max_size)
+}
+
+/// Juniper `policy-options prefix-list` stanza; with `swap` the stanza replaces the list.
+pub fn juniper_formatter(event: &DumpEvent, options: &FormatOptions) -> Vec<String> {
+    let lines = match *event {
+        DumpEvent::Begin(_) => {
+            let replace = if options.swap { "replace: " } else { "" };
+            vec!["policy-options {".to_owned(), format!("    {}prefix-list {} {{", replace, options.list_name)]
+        }
+        DumpEvent::Chunk(_, ref cidrs) => cidrs.iter().map(|cidr| format!("        {};", make_cidr_ip_string(cidr))).collect(),
+        DumpEvent::End(_) => vec!["    }".to_owned(), "}".to_owned()]
+    };
+    pack(lines, "\n", options.max_size)
+}
+
+/// BIRD prefix set constant. Entries after the first one are prefixed by comma, as chunks are
+/// formatted independently. BIRD has no empty prefix set, so an empty dump renders nothing.
+pub fn bird_formatter(event: &DumpEvent, options: &FormatOptions) -> Vec<String> {
+    let lines = match *event {
+        DumpEvent::Begin(ref info) | DumpEvent::End(ref info) if info.total == 0 => Vec::new(),
+        DumpEvent::Begin(_) => vec![format!("define {} = [", options.list_name)],
+        DumpEvent::Chunk(offset, ref cidrs) => cidrs.iter().enumerate().map(|(idx, cidr)| {
+            let separator = if offset + idx == 0 { "  " } else { ", " };
+            format!("  {}{}", separator, make_cidr_ip_string(cidr))
+        }).collect(),
+        DumpEvent::End(_) => vec!["];".to_owned()]
+    };
+    pack(lines, "\n", options.max_size)
+}
+
+#[derive(Serialize)]
+struct JsonPrefix {
+    network: String,
+    prefix_len: u8,
+    first: String,
+    last: String,
+    count: u64,
+}
+
+impl JsonPrefix {
+    fn new(cidr: &(u32, u8)) -> JsonPrefix {
+        let (first, last) = cidr_bounds(cidr);
+        JsonPrefix {
+            network: make_ip_string(cidr.0),
+            prefix_len: cidr.1,
+            first: make_ip_string(first),
+            last: make_ip_string(last),
+            count: (last - first) as u64 + 1,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonDumpInfo {
+    sequence: u64,
+    window_start: u64,
+    window_end: u64,
+    total: usize,
+}
+
+impl JsonDumpInfo {
+    fn new(info: &DumpInfo) -> JsonDumpInfo {
+        JsonDumpInfo { sequence: info.sequence, window_start: info.window_start, window_end: info.timestamp, total: info.total }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum NdjsonRecord {
+    Begin(JsonDumpInfo),
+    Prefix(JsonPrefix),
+    End(JsonDumpInfo),
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+/// One JSON document per dump, `{"sequence": .., "window_start": .., "window_end": .., "total": ..,
+/// "prefixes": [..]}`, sent as a single message at the end of the dump regardless of `max_size`.
+pub struct JsonFormatter {
+    prefixes: Vec<String>,
+}
+
+impl JsonFormatter {
+    pub fn new() -> JsonFormatter {
+        JsonFormatter { prefixes: Vec::new() }
+    }
+}
+
+impl Formatter for JsonFormatter {
+    fn begin(&mut self, _: &DumpInfo) -> Vec<Vec<u8>> {
+        self.prefixes.clear();
+        Vec::new()
+    }
+
+    fn chunk(&mut self, _: usize, cidrs: &[(u32, u8)]) -> Vec<Vec<u8>> {
+        self.prefixes.extend(cidrs.iter().map(|cidr| to_json(&JsonPrefix::new(cidr))));
+        Vec::new()
+    }
+
+    fn end(&mut self, info: &DumpInfo) -> Vec<Vec<u8>> {
+        let head = to_json(&JsonDumpInfo::new(info));
+        let document = format!("{},\"prefixes\":[{}]}}", &head[..head.len() - 1], self.prefixes.join(","));
+        self.prefixes.clear();
+        vec![document.into_bytes()]
+    }
+
+    fn bounded(&self) -> bool {
+        false
+    }
+
+    fn single_line(&self) -> bool {
+        true
+    }
+
+    fn stateful(&self) -> bool {
+        true
+    }
+}
+
+/// Newline delimited JSON, one object per line tagged by `type`: `begin` and `end` carry dump
+/// info, `prefix` lines carry one range each.
+pub fn ndjson_formatter(event: &DumpEvent, options: &FormatOptions) -> Vec<String> {
+    let lines = match *event {
+        DumpEvent::Begin(ref info) => vec![to_json(&NdjsonRecord::Begin(JsonDumpInfo::new(info)))],
+        DumpEvent::Chunk(_, ref cidrs) => cidrs.iter().map(|cidr| to_json(&NdjsonRecord::Prefix(JsonPrefix::new(cidr)))).collect(),
+        DumpEvent::End(ref info) => vec![to_json(&NdjsonRecord::End(JsonDumpInfo::new(info)))],
+    };
+    pack(lines, "\n", options.max_size)
+}
+
+/// First and last address of `cidr`.
+pub fn cidr_bounds(cidr: &(u32, u8)) -> (u32, u32) {
+    let (network, len) = *cidr;
+    let host_mask = if len >= 32 { 0 } else { u32::max_value() >> len };
+    (network & !host_mask, network | host_mask)
+}
+
+/// Collapses sorted CIDRs into maximal ranges of consecutive addresses.
+pub fn collapse_ranges(cidrs: &[(u32, u8)]) -> Vec<(u32, u32)> {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for cidr in cidrs {
+        let (first, last) = cidr_bounds(cidr);
+        match ranges.last_mut() {
+            Some(range) if range.1 != u32::max_value() && range.1 + 1 >= first => {
+                if last > range.1 {
+                    range.1 = last;
+                }
+                continue;
+            }
+            _ => {}
+        }
+        ranges.push((first, last));
+    }
+    ranges
+}
+
+/// `start-end` ranges made of adjacent CIDRs, one per line. The last range of a chunk is held
+/// back, as it may continue in the next chunk.
+pub struct RangeFormatter {
+    options: FormatOptions,
+    pending: Option<(u32, u32)>,
+}
+
+impl RangeFormatter {
+    pub fn new(options: FormatOptions) -> RangeFormatter {
+        RangeFormatter { options, pending: None }
+    }
+
+    fn pack_ranges(&self, ranges: &[(u32, u32)]) -> Vec<Vec<u8>> {
+        let lines = ranges.iter()
+            .map(|&(first, last)| format!("{}-{}", make_ip_string(first), make_ip_string(last)))
+            .collect();
+        pack(lines, "\n", self.options.max_size).into_iter().map(String::into_bytes).collect()
+    }
+}
+
+impl Formatter for RangeFormatter {
+    fn begin(&mut self, _: &DumpInfo) -> Vec<Vec<u8>> {
+        self.pending = None;
+        Vec::new()
+    }
+
+    fn chunk(&mut self, _: usize, cidrs: &[(u32, u8)]) -> Vec<Vec<u8>> {
+        let mut ranges = collapse_ranges(cidrs);
+        if let Some((first, last)) = self.pending.take() {
+            match ranges.first_mut() {
+                Some(range) if last != u32::max_value() && last + 1 >= range.0 => {
+                    range.0 = first;
+                    if last > range.1 {
+                        range.1 = last;
+                    }
+                }
+                _ => ranges.insert(0, (first, last))
+            }
+        }
+        self.pending = ranges.pop();
+        self.pack_ranges(&ranges)
+    }
+
+    fn end(&mut self, _: &DumpInfo) -> Vec<Vec<u8>> {
+        match self.pending.take() {
+            Some(range) => self.pack_ranges(&[range]),
+            None => Vec::new()
+        }
+    }
+
+    fn stateful(&self) -> bool {
+        true
+    }
+}
+
+/// Every CIDR printed as its `first-last` address range, one per line.
+pub fn cidr_range_formatter(event: &DumpEvent, options: &FormatOptions) -> Vec<String> {
+    match *event {
+        DumpEvent::Chunk(_, ref cidrs) => {
+            let lines = cidrs.iter().map(cidr_bounds)
+                .map(|(first, last)| format!("{}-{}", make_ip_string(first), make_ip_string(last)))
+                .collect();
+            pack(lines, "\n", options.max_size)
+        }
+        _ => Vec::new()
+    }
+}
+
+pub const BINARY_MAGIC: [u8; 2] = *b"IA";
+pub const BINARY_VERSION: u8 = 2;
+pub const BINARY_HEADER_SIZE: usize = 16;
+pub const BINARY_KIND_BEGIN: u8 = 1;
+pub const BINARY_KIND_IPV4: u8 = 2;
+pub const BINARY_KIND_END: u8 = 4;
+const BINARY_IPV4_ENTRY_SIZE: usize = 5;
+const BINARY_MARKER_SIZE: usize = 24;
+
+/// Binary messages with header of magic `IA`, version byte, kind byte, big endian u32 length of
+/// the payload following the header and big endian u64 dump sequence. Begin and end messages
+/// carry window start, timestamp and total as big endian u64. Prefix messages carry 5 byte
+/// entries, 4 byte network and 1 byte length, as many as fit `max_size`.
+pub struct BinaryFormatter {
+    options: FormatOptions,
+    sequence: u64,
+}
+
+impl BinaryFormatter {
+    pub fn new(options: FormatOptions) -> BinaryFormatter {
+        BinaryFormatter { options, sequence: 0 }
+    }
+
+    fn header(&self, kind: u8, payload_size: usize) -> Vec<u8> {
+        let mut message = Vec::with_capacity(BINARY_HEADER_SIZE + payload_size);
+        message.extend_from_slice(&BINARY_MAGIC);
+        message.push(BINARY_VERSION);
+        message.push(kind);
+        push_u32(&mut message, payload_size as u32);
+        push_u64(&mut message, self.sequence);
+        message
+    }
+
+    fn marker(&mut self, kind: u8, info: &DumpInfo) -> Vec<Vec<u8>> {
+        self.sequence = info.sequence;
+        let mut message = self.header(kind, BINARY_MARKER_SIZE);
+        push_u64(&mut message, info.window_start);
+        push_u64(&mut message, info.timestamp);
+        push_u64(&mut message, info.total as u64);
+        vec![message]
+    }
+}
+
+fn push_u32(message: &mut Vec<u8>, value: u32) {
+    for shift in (0..4).rev() {
+        message.push((value >> (shift * 8)) as u8);
+    }
+}
+
+fn push_u64(message: &mut Vec<u8>, value: u64) {
+    for shift in (0..8).rev() {
+        message.push((value >> (shift * 8)) as u8);
+    }
+}
+
+impl Formatter for BinaryFormatter {
+    fn begin(&mut self, info: &DumpInfo) -> Vec<Vec<u8>> {
+        self.marker(BINARY_KIND_BEGIN, info)
+    }
+
+    fn chunk(&mut self, _: usize, cidrs: &[(u32, u8)]) -> Vec<Vec<u8>> {
+        let per_message = cmp::max(1, self.options.max_size.saturating_sub(BINARY_HEADER_SIZE) / BINARY_IPV4_ENTRY_SIZE);
+        cidrs.chunks(per_message).map(|entries| {
+            let mut message = self.header(BINARY_KIND_IPV4, entries.len() * BINARY_IPV4_ENTRY_SIZE);
+            for &(network, len) in entries {
+                message.extend_from_slice(&[(network >> 24) as u8, (network >> 16) as u8, (network >> 8) as u8, network as u8, len]);
+            }
+            message
+        }).collect()
+    }
+
+    fn end(&mut self, info: &DumpInfo) -> Vec<Vec<u8>> {
+        self.marker(BINARY_KIND_END, info)
+    }
+
+    fn binary(&self) -> bool {
+        true
+    }
+}
+
+fn default_template_separator() -> String {
+    "\n".to_owned()
+}
+
+fn default_template_escape() -> String {
+    "none".to_owned()
+}
+
+const TEMPLATE_LINE_FIELDS: [&str; 8] = ["network", "len", "cidr", "first", "last", "count", "index", "name"];
+const TEMPLATE_DUMP_FIELDS: [&str; 5] = ["sequence", "window_start", "timestamp", "total", "name"];
+
+#[derive(PartialEq, Eq, Debug)]
+enum Segment {
+    Text(String),
+    Field(String),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Escape {
+    None,
+    /// Backslash escapes for use inside JSON strings.
+    Json,
+    /// Single quoted shell word.
+    Shell,
+    /// XML entities.
+    Xml,
+}
+
+impl Escape {
+    fn from_name(name: &str) -> Result<Escape, String> {
+        match name {
+            "none" => Ok(Escape::None),
+            "json" => Ok(Escape::Json),
+            "shell" => Ok(Escape::Shell),
+            "xml" => Ok(Escape::Xml),
+            other => Err(format!("Unknown template escape `{}` specified!", other))
+        }
+    }
+
+    fn apply(&self, value: &str) -> String {
+        match *self {
+            Escape::None => value.to_owned(),
+            Escape::Json => {
+                let quoted = to_json(&value);
+                quoted[1..quoted.len() - 1].to_owned()
+            }
+            Escape::Shell => format!("'{}'", value.replace('\'', "'\\''")),
+            Escape::Xml => value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+                .replace('"', "&quot;").replace('\'', "&apos;"),
+        }
     }
-    concated_msg
 }
 
-fn concat_to_size(strings: &[(u32, u8)], max_size: usize) -> (String, usize) {
-    let mut tmp_size: usize = 0;
-    let mut chunk_last_idx: usize = 0;
-    let mut use_entire_slice = true;
-    for (idx, cidr) in strings.iter().enumerate() {
-        let cidr_str = make_cidr_ip_string(cidr);
-        chunk_last_idx = idx;
-        if tmp_size > 0 {
-            tmp_size += 1;
+/// Splits `template` into text and `{field}` placeholders; `{{` and `}}` stand for literal braces.
+fn parse_template(template: &str, fields: &[&str]) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("Unclosed `{{` in template `{}`", template))
+                    }
+                }
+                if !fields.contains(&name.as_str()) {
+                    return Err(format!("Unknown field `{}` in template `{}`; Expected one of {:?}", name, template, fields));
+                }
+                if !text.is_empty() {
+                    segments.push(Segment::Text(mem::replace(&mut text, String::new())));
+                }
+                segments.push(Segment::Field(name));
+            }
+            '}' => return Err(format!("Unmatched `}}` in template `{}`", template)),
+            c => text.push(c)
         }
-        if cidr_str.len() + tmp_size > max_size {
-            use_entire_slice = false;
-            break;
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    Ok(segments)
+}
+
+fn render<F: Fn(&str) -> String>(segments: &[Segment], escape: Escape, value_of: F) -> String {
+    segments.iter().map(|segment| {
+        match *segment {
+            Segment::Text(ref text) => text.clone(),
+            Segment::Field(ref name) => escape.apply(&value_of(name)),
         }
-        tmp_size += cidr_str.len();
+    }).collect()
+}
+
+/// Renders `line` template for every prefix, joined by `separator`, between rendered `header`
+/// and `footer`. Lines are fields `network`, `len`, `cidr`, `first`, `last`, `count`, `index`
+/// and `name`; header and footer `sequence`, `window_start`, `timestamp`, `total` and `name`.
+pub struct TemplateFormatter {
+    line: Vec<Segment>,
+    header: Vec<Segment>,
+    footer: Vec<Segment>,
+    separator: String,
+    escape: Escape,
+    options: FormatOptions,
+    single_line: bool,
+}
+
+impl TemplateFormatter {
+    pub fn new(options: FormatOptions) -> Result<TemplateFormatter, String> {
+        let settings = match options.template {
+            Some(ref settings) => settings.clone(),
+            None => return Err("Expected template formatter, but couldn't find template config".to_owned())
+        };
+        let single_line = ![&settings.line, &settings.header, &settings.footer, &settings.separator]
+            .iter().any(|part| part.contains('\n'));
+        Ok(TemplateFormatter {
+            line: parse_template(&settings.line, &TEMPLATE_LINE_FIELDS)?,
+            header: parse_template(&settings.header, &TEMPLATE_DUMP_FIELDS)?,
+            footer: parse_template(&settings.footer, &TEMPLATE_DUMP_FIELDS)?,
+            separator: settings.separator,
+            escape: Escape::from_name(&settings.escape)?,
+            options,
+            single_line,
+        })
     }
-    if use_entire_slice {
-        let cidr_ips: Vec<String> = strings.iter().map(|cidr| {make_cidr_ip_string(cidr)}).collect();
-        (cidr_ips.join(" "), strings.len())
-    } else {
-        let cidr_ips: Vec<String> = strings[..chunk_last_idx].iter().map(|cidr|{make_cidr_ip_string(cidr)}).collect();
-        (cidr_ips.join(" "), chunk_last_idx)
+
+    fn render_dump(&self, segments: &[Segment], info: &DumpInfo) -> Vec<Vec<u8>> {
+        if segments.is_empty() {
+            return Vec::new();
+        }
+        let rendered = render(segments, self.escape, |name| {
+            match name {
+                "sequence" => info.sequence.to_string(),
+                "window_start" => info.window_start.to_string(),
+                "timestamp" => info.timestamp.to_string(),
+                "total" => info.total.to_string(),
+                _ => self.options.list_name.clone(),
+            }
+        });
+        vec![rendered.into_bytes()]
     }
 }
 
-fn make_cidr_ip_string(cidr: &(u32, u8)) -> String {
+impl Formatter for TemplateFormatter {
+    fn begin(&mut self, info: &DumpInfo) -> Vec<Vec<u8>> {
+        self.render_dump(&self.header, info)
+    }
+
+    fn chunk(&mut self, offset: usize, cidrs: &[(u32, u8)]) -> Vec<Vec<u8>> {
+        let lines = cidrs.iter().enumerate().map(|(idx, cidr)| {
+            let (first, last) = cidr_bounds(cidr);
+            render(&self.line, self.escape, |name| {
+                match name {
+                    "network" => make_ip_string(cidr.0),
+                    "len" => cidr.1.to_string(),
+                    "cidr" => make_cidr_ip_string(cidr),
+                    "first" => make_ip_string(first),
+                    "last" => make_ip_string(last),
+                    "count" => ((last - first) as u64 + 1).to_string(),
+                    "index" => (offset + idx).to_string(),
+                    _ => self.options.list_name.clone(),
+                }
+            })
+        }).collect();
+        pack(lines, &self.separator, self.options.max_size).into_iter().map(String::into_bytes).collect()
+    }
+
+    fn end(&mut self, info: &DumpInfo) -> Vec<Vec<u8>> {
+        self.render_dump(&self.footer, info)
+    }
+
+    fn single_line(&self) -> bool {
+        self.single_line
+    }
+}
+
+fn make_ip_string(ip: u32) -> String {
+    format!("{}.{}.{}.{}", ip >> 24, (ip >> 16) & 0xff, (ip >> 8) & 0xff, ip & 0xff)
+}
+
+pub fn make_cidr_ip_string(cidr: &(u32, u8)) -> String {
     format!("{}.{}.{}.{}/{}", cidr.0 >> 24, (cidr.0 >> 16) & 0xff, (cidr.0 >> 8) & 0xff, cidr.0 & 0xff, cidr.1)
 }
 
@@ -50,35 +791,278 @@ mod tests {
     }
 
     #[test]
-    fn test_concat_to_size() {
-        let addresses = vec![
-            (make_ip(192, 168, 1, 1), 32_u8),
-            (make_ip(172, 16, 100, 0), 24_u8),
-            (make_ip(10, 10, 0, 0), 16_u8),
-            (make_ip(20, 0, 0, 0), 8_u8)
-        ];
-        assert_eq!(("192.168.1.1/32".to_owned(), 1), concat_to_size(&addresses, 20));
+    fn test_formatter_by_name() {
+        assert!(formatter_by_name("simple", FormatOptions::default()).is_ok());
+        assert!(formatter_by_name("ipset", FormatOptions::default()).is_ok());
+        assert!(formatter_by_name("nft", FormatOptions::default()).is_ok());
+        assert!(formatter_by_name("cisco_prefix_list", FormatOptions::default()).is_ok());
+        assert!(formatter_by_name("bird", FormatOptions::default()).is_ok());
+        assert!(formatter_by_name("json", FormatOptions::default()).is_ok());
+        assert!(formatter_by_name("ndjson", FormatOptions::default()).is_ok());
+        assert!(formatter_by_name("range", FormatOptions::default()).is_ok());
+        assert!(formatter_by_name("xml", FormatOptions::default()).is_err());
     }
 
     #[test]
-    fn test_concat_to_size_with_exact_len() {
-        let addresses = vec![
-            (make_ip(192, 168, 1, 1), 32_u8),
-            (make_ip(172, 16, 100, 0), 24_u8),
-            (make_ip(10, 10, 0, 0), 16_u8),
-            (make_ip(20, 0, 0, 0), 8_u8)
-        ];
-        assert_eq!(("192.168.1.1/32 172.16.100.0/24".to_owned(), 2), concat_to_size(&addresses, 30));
+    fn test_formatter_capabilities() {
+        let formatter = |name: &str, options: FormatOptions| formatter_by_name(name, options).unwrap();
+        let json = formatter("json", FormatOptions::default());
+        assert!(!json.bounded() && json.single_line() && json.stateful());
+        let range = formatter("range", FormatOptions::default());
+        assert!(range.bounded() && !range.single_line() && range.stateful());
+        assert!(formatter("binary", FormatOptions::default()).binary());
+        assert!(formatter("simple", FormatOptions::default()).single_line());
+        let ipset = formatter("ipset", FormatOptions::default());
+        assert!(ipset.bounded() && !ipset.binary() && !ipset.single_line() && !ipset.stateful());
+        assert!(formatter("template", template_options("{cidr}", "", "", " ", "none")).single_line());
+        assert!(!formatter("template", template_options("{cidr}", "", "", "\n", "none")).single_line());
+    }
+
+    #[test]
+    fn test_simple_formatter_markers() {
+        let info = DumpInfo { sequence: 7, window_start: 0, timestamp: 1500000000, total: 2 };
+        let options = FormatOptions::default();
+        assert_eq!(vec!["BEGIN 7 1500000000 2".to_owned()], simple_formatter(&DumpEvent::Begin(info), &options));
+        assert_eq!(vec!["10.0.0.0/8 192.168.0.0/16".to_owned()],
+                   simple_formatter(&DumpEvent::Chunk(0, vec![(make_ip(10, 0, 0, 0), 8), (make_ip(192, 168, 0, 0), 16)]), &options));
+        assert_eq!(vec!["END 7 1500000000 2".to_owned()], simple_formatter(&DumpEvent::End(info), &options));
+    }
+
+    #[test]
+    fn test_simple_formatter_max_size() {
+        let cidrs = vec![(make_ip(10, 0, 0, 0), 8), (make_ip(192, 168, 0, 0), 16), (make_ip(172, 16, 0, 0), 12), (make_ip(10, 1, 0, 0), 16)];
+        let options = FormatOptions { max_size: 30, ..FormatOptions::default() };
+        assert_eq!(vec!["10.0.0.0/8 192.168.0.0/16".to_owned(), "172.16.0.0/12 10.1.0.0/16".to_owned()],
+                   simple_formatter(&DumpEvent::Chunk(0, cidrs), &options));
+    }
+
+    fn format_dump(formatter: AggFormatter, options: &FormatOptions, cidrs: Vec<(u32, u8)>) -> Vec<String> {
+        let info = DumpInfo { sequence: 1, window_start: 0, timestamp: 1500000000, total: cidrs.len() };
+        let mut messages = formatter(&DumpEvent::Begin(info), options);
+        messages.extend(formatter(&DumpEvent::Chunk(0, cidrs), options));
+        messages.extend(formatter(&DumpEvent::End(info), options));
+        messages
+    }
+
+    fn format_chunks(formatter: &mut Formatter, chunks: Vec<Vec<(u32, u8)>>) -> Vec<String> {
+        let total = chunks.iter().map(|chunk| chunk.len()).sum();
+        let info = DumpInfo { sequence: 1, window_start: 0, timestamp: 1500000000, total };
+        let mut messages = formatter.begin(&info);
+        let mut offset = 0;
+        for chunk in chunks {
+            messages.extend(formatter.chunk(offset, &chunk));
+            offset += chunk.len();
+        }
+        messages.extend(formatter.end(&info));
+        messages.into_iter().map(|message| String::from_utf8(message).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_pack() {
+        let items = vec!["aaaa".to_owned(), "bb".to_owned(), "cccccccc".to_owned(), "d".to_owned()];
+        assert_eq!(vec!["aaaa bb", "cccccccc", "d"], pack(items.clone(), " ", 7));
+        assert_eq!(vec!["aaaa bb cccccccc d"], pack(items, " ", 100));
+        assert!(pack(Vec::new(), " ", 7).is_empty());
+    }
+
+    #[test]
+    fn test_ipset_formatter() {
+        let cidrs = vec![(make_ip(10, 0, 0, 0), 8), (make_ip(192, 168, 0, 0), 16)];
+        let mut options = FormatOptions { max_size: 1024, list_name: "block".to_owned(), ..FormatOptions::default() };
+        assert_eq!(vec!["create block hash:net family inet -exist", "add block 10.0.0.0/8 -exist\nadd block 192.168.0.0/16 -exist"],
+                   format_dump(ipset_formatter, &options, cidrs.clone()));
+        options.swap = true;
+        assert_eq!(vec!["create block hash:net family inet -exist",
+                        "create block-swap hash:net family inet -exist",
+                        "flush block-swap",
+                        "add block-swap 10.0.0.0/8 -exist",
+                        "add block-swap 192.168.0.0/16 -exist",
+                        "swap block-swap block",
+                        "destroy block-swap"],
+                   format_dump(ipset_formatter, &options, cidrs).join("\n").lines().collect::<Vec<&str>>());
+    }
+
+    #[test]
+    fn test_nft_formatter() {
+        let cidrs = vec![(make_ip(10, 0, 0, 0), 8), (make_ip(192, 168, 0, 0), 16), (make_ip(172, 16, 0, 0), 12)];
+        let options = FormatOptions { max_size: 70, list_name: "blocklist".to_owned(), swap: true, ..FormatOptions::default() };
+        assert_eq!(vec!["add set inet filter blocklist { type ipv4_addr; flags interval; }",
+                        "flush set inet filter blocklist",
+                        "add element inet filter blocklist { 10.0.0.0/8, 192.168.0.0/16 }",
+                        "add element inet filter blocklist { 172.16.0.0/12 }"],
+                   format_dump(nft_formatter, &options, cidrs));
+    }
+
+    #[test]
+    fn test_router_formatters() {
+        let cidrs = vec![(make_ip(10, 0, 0, 0), 8), (make_ip(192, 168, 1, 0), 24)];
+        let options = FormatOptions { max_size: 1024, list_name: "agg".to_owned(), swap: true, seq_start: 10, seq_step: 10, ..FormatOptions::default() };
+        assert_eq!("no ip prefix-list agg\n\
+                    ip prefix-list agg seq 10 permit 10.0.0.0/8\n\
+                    ip prefix-list agg seq 20 permit 192.168.1.0/24",
+                   format_dump(cisco_prefix_list_formatter, &options, cidrs.clone()).join("\n"));
+        assert_eq!("no ip access-list standard agg\n\
+                    ip access-list standard agg\n \
+                    10 permit 10.0.0.0 0.255.255.255\n \
+                    20 permit 192.168.1.0 0.0.0.255",
+                   format_dump(cisco_acl_formatter, &options, cidrs.clone()).join("\n"));
+        assert_eq!("policy-options {\n    replace: prefix-list agg {\n        10.0.0.0/8;\n        192.168.1.0/24;\n    }\n}",
+                   format_dump(juniper_formatter, &options, cidrs.clone()).join("\n"));
+        assert_eq!("define agg = [\n    10.0.0.0/8\n  , 192.168.1.0/24\n];",
+                   format_dump(bird_formatter, &options, cidrs).join("\n"));
+        assert!(format_dump(bird_formatter, &options, Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_router_formatter_sequence_follows_chunk_offset() {
+        let options = FormatOptions::default();
+        assert_eq!(vec!["ip prefix-list ipaggregator seq 5005 permit 10.0.0.0/8"],
+                   cisco_prefix_list_formatter(&DumpEvent::Chunk(1000, vec![(make_ip(10, 0, 0, 0), 8)]), &options));
+    }
+
+    #[test]
+    fn test_json_formatter() {
+        let cidrs = vec![(make_ip(10, 0, 0, 0), 8), (make_ip(192, 168, 1, 1), 32)];
+        let messages = format_chunks(&mut JsonFormatter::new(), vec![cidrs[..1].to_vec(), cidrs[1..].to_vec()]);
+        assert_eq!(1, messages.len());
+        let document = messages.concat();
+        assert_eq!("{\"sequence\":1,\"window_start\":0,\"window_end\":1500000000,\"total\":2,\"prefixes\":[\
+                    {\"network\":\"10.0.0.0\",\"prefix_len\":8,\"first\":\"10.0.0.0\",\"last\":\"10.255.255.255\",\"count\":16777216},\
+                    {\"network\":\"192.168.1.1\",\"prefix_len\":32,\"first\":\"192.168.1.1\",\"last\":\"192.168.1.1\",\"count\":1}]}",
+                   document);
+        assert!(serde_json::from_str::<serde_json::Value>(&document).is_ok());
+    }
+
+    #[test]
+    fn test_ndjson_formatter() {
+        let cidrs = vec![(make_ip(172, 16, 0, 0), 12)];
+        let lines = format_dump(ndjson_formatter, &FormatOptions::default(), cidrs).join("\n");
+        assert_eq!(vec!["{\"type\":\"begin\",\"sequence\":1,\"window_start\":0,\"window_end\":1500000000,\"total\":1}",
+                        "{\"type\":\"prefix\",\"network\":\"172.16.0.0\",\"prefix_len\":12,\"first\":\"172.16.0.0\",\"last\":\"172.31.255.255\",\"count\":1048576}",
+                        "{\"type\":\"end\",\"sequence\":1,\"window_start\":0,\"window_end\":1500000000,\"total\":1}"],
+                   lines.lines().collect::<Vec<&str>>());
+    }
+
+    #[test]
+    fn test_collapse_ranges() {
+        let cidrs = vec![
+            (make_ip(10, 0, 0, 0), 24),
+            (make_ip(10, 0, 1, 0), 24),
+            (make_ip(10, 0, 2, 0), 32),
+            (make_ip(10, 0, 2, 2), 31),
+            (make_ip(255, 255, 255, 255), 32)];
+        assert_eq!(vec![(make_ip(10, 0, 0, 0), make_ip(10, 0, 2, 0)),
+                        (make_ip(10, 0, 2, 2), make_ip(10, 0, 2, 3)),
+                        (make_ip(255, 255, 255, 255), make_ip(255, 255, 255, 255))],
+                   collapse_ranges(&cidrs));
+        assert!(collapse_ranges(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_cidr_range_formatter() {
+        let chunk = DumpEvent::Chunk(0, vec![(make_ip(10, 0, 0, 0), 24), (make_ip(10, 0, 1, 0), 24), (make_ip(192, 168, 1, 1), 32)]);
+        assert_eq!(vec!["10.0.0.0-10.0.0.255\n10.0.1.0-10.0.1.255\n192.168.1.1-192.168.1.1"],
+                   cidr_range_formatter(&chunk, &FormatOptions::default()));
+    }
+
+    #[test]
+    fn test_range_formatter_merges_across_chunks() {
+        let chunks = vec![
+            vec![(make_ip(10, 0, 0, 0), 24), (make_ip(10, 0, 1, 0), 24)],
+            vec![(make_ip(10, 0, 2, 0), 23)],
+            vec![(make_ip(192, 168, 1, 1), 32)]];
+        assert_eq!(vec!["10.0.0.0-10.0.3.255", "192.168.1.1-192.168.1.1"],
+                   format_chunks(&mut RangeFormatter::new(FormatOptions::default()), chunks));
+    }
+
+    #[test]
+    fn test_binary_formatter() {
+        let options = FormatOptions { max_size: 26, ..FormatOptions::default() };
+        let mut formatter = BinaryFormatter::new(options);
+        let info = DumpInfo { sequence: 3, window_start: 1, timestamp: 2, total: 3 };
+        assert_eq!(vec![b"IA\x02\x01\0\0\0\x18\0\0\0\0\0\0\0\x03\0\0\0\0\0\0\0\x01\0\0\0\0\0\0\0\x02\0\0\0\0\0\0\0\x03".to_vec()],
+                   formatter.begin(&info));
+        let cidrs = vec![(make_ip(10, 0, 0, 0), 8), (make_ip(192, 168, 1, 1), 32), (make_ip(172, 16, 0, 0), 12)];
+        assert_eq!(vec![b"IA\x02\x02\0\0\0\x0a\0\0\0\0\0\0\0\x03\x0a\0\0\0\x08\xc0\xa8\x01\x01\x20".to_vec(),
+                        b"IA\x02\x02\0\0\0\x05\0\0\0\0\0\0\0\x03\xac\x10\0\0\x0c".to_vec()],
+                   formatter.chunk(0, &cidrs));
+        assert_eq!(BINARY_KIND_END, formatter.end(&info)[0][3]);
+    }
+
+    fn template_options(line: &str, header: &str, footer: &str, separator: &str, escape: &str) -> FormatOptions {
+        let template = TemplateSettings {
+            line: line.to_owned(),
+            header: header.to_owned(),
+            footer: footer.to_owned(),
+            separator: separator.to_owned(),
+            escape: escape.to_owned(),
+        };
+        FormatOptions { max_size: 1024, template: Some(template), ..FormatOptions::default() }
+    }
+
+    #[test]
+    fn test_template_formatter() {
+        let options = template_options("deny {network}/{len}; # {count}", "# dump {sequence} of {total}", "# end", "\n", "none");
+        let chunks = vec![vec![(make_ip(10, 0, 0, 0), 8), (make_ip(10, 1, 0, 0), 16)], vec![(make_ip(192, 168, 1, 1), 32)]];
+        assert_eq!(vec!["# dump 1 of 3", "deny 10.0.0.0/8; # 16777216\ndeny 10.1.0.0/16; # 65536", "deny 192.168.1.1/32; # 1", "# end"],
+                   format_chunks(&mut TemplateFormatter::new(options).unwrap(), chunks));
+    }
+
+    #[test]
+    fn test_template_formatter_escape_and_braces() {
+        let mut options = template_options("{{\"set\": \"{name}\", \"range\": \"{first}-{last}\"}}", "", "", ",", "json");
+        options.list_name = "say \"hi\"".to_owned();
+        assert_eq!(vec!["{\"set\": \"say \\\"hi\\\"\", \"range\": \"10.0.0.0-10.0.0.255\"}"],
+                   format_chunks(&mut TemplateFormatter::new(options).unwrap(), vec![vec![(make_ip(10, 0, 0, 0), 24)]]));
+        assert_eq!("'it'\\''s'", Escape::Shell.apply("it's"));
+        assert_eq!("a&amp;b&lt;", Escape::Xml.apply("a&b<"));
+    }
+
+    #[test]
+    fn test_template_formatter_invalid() {
+        assert!(TemplateFormatter::new(template_options("{network", "", "", "\n", "none")).is_err());
+        assert!(TemplateFormatter::new(template_options("{netmask}", "", "", "\n", "none")).is_err());
+        assert!(TemplateFormatter::new(template_options("{network}", "{network}", "", "\n", "none")).is_err());
+        assert!(TemplateFormatter::new(template_options("{network}", "", "", "\n", "html")).is_err());
+        assert!(TemplateFormatter::new(FormatOptions::default()).is_err());
+    }
+
+    fn cidr_strings(cidrs: &[(u32, u8)]) -> Vec<String> {
+        cidrs.iter().map(make_cidr_ip_string).collect()
+    }
+
+    #[test]
+    fn test_pack_single_cidr() {
+        assert_eq!(vec!["10.0.0.0/8".to_owned()], pack(cidr_strings(&[(make_ip(10, 0, 0, 0), 8)]), " ", MAX_UDP_DATAGRAM_PAYLOAD_SIZE));
+    }
+
+    #[test]
+    fn test_pack_oversize_cidrs() {
+        let addresses = vec![(make_ip(10, 0, 0, 0), 8), (make_ip(192, 168, 100, 100), 32), (make_ip(10, 1, 0, 0), 16)];
+        assert_eq!(vec!["10.0.0.0/8".to_owned(), "192.168.100.100/32".to_owned(), "10.1.0.0/16".to_owned()],
+                   pack(cidr_strings(&addresses), " ", 12));
+        assert_eq!(vec!["10.0.0.0/8".to_owned(), "192.168.100.100/32".to_owned(), "10.1.0.0/16".to_owned()],
+                   pack(cidr_strings(&addresses), " ", 5));
+    }
+
+    #[test]
+    fn test_pack_fills_messages() {
+        let addresses: Vec<(u32, u8)> = (0..1000).map(|idx| (make_ip(10, 0, (idx / 256) as u8, (idx % 256) as u8), 32)).collect();
+        let messages = pack(cidr_strings(&addresses), " ", MAX_UDP_DATAGRAM_PAYLOAD_SIZE);
+        assert!(messages.iter().all(|message| message.len() <= MAX_UDP_DATAGRAM_PAYLOAD_SIZE));
+        assert_eq!(1000, messages.iter().map(|message| message.split(' ').count()).sum::<usize>());
+        assert_eq!("10.0.3.231/32", messages.last().unwrap().split(' ').last().unwrap());
     }
 
     #[test]
-    fn test_concat_to_size_too_short() {
+    fn test_pack_cidrs_to_size() {
         let addresses = vec![
             (make_ip(192, 168, 1, 1), 32_u8),
             (make_ip(172, 16, 100, 0), 24_u8),
             (make_ip(10, 10, 0, 0), 16_u8),
             (make_ip(20, 0, 0, 0), 8_u8)
         ];
-        assert_eq!(("".to_owned(), 0), concat_to_size(&addresses, 5));
+        assert_eq!("192.168.1.1/32", pack(cidr_strings(&addresses), " ", 20)[0]);
+        assert_eq!("192.168.1.1/32 172.16.100.0/24", pack(cidr_strings(&addresses), " ", 30)[0]);
     }
 }
\ No newline at end of file
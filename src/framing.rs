@@ -0,0 +1,131 @@
+use std::io;
+use std::io::{BufRead, Error, ErrorKind, Read, Write};
+
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Framing {
+    Newline,
+    LengthPrefixed,
+}
+
+impl Framing {
+    pub fn from_name(name: &str) -> Result<Framing, String> {
+        match name {
+            "newline" => Ok(Framing::Newline),
+            "length_prefixed" => Ok(Framing::LengthPrefixed),
+            other => Err(format!("Unknown framing `{}` specified!", other))
+        }
+    }
+
+    /// Reads one frame from `reader`. Returns `Ok(None)` once the stream is closed.
+    pub fn read_frame<R: BufRead>(&self, reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+        match *self {
+            Framing::Newline => read_line_frame(reader),
+            Framing::LengthPrefixed => read_length_prefixed_frame(reader),
+        }
+    }
+
+    /// Writes `frame` to `writer` so that `read_frame` of the same framing returns it back.
+    pub fn write_frame<W: Write>(&self, writer: &mut W, frame: &[u8]) -> io::Result<()> {
+        match *self {
+            Framing::Newline => {
+                if frame.contains(&b'\n') {
+                    return Err(Error::new(ErrorKind::InvalidInput, "Newline framed frame contains newline"));
+                }
+                writer.write_all(frame)?;
+                writer.write_all(b"\n")
+            }
+            Framing::LengthPrefixed => {
+                if frame.len() > MAX_FRAME_SIZE {
+                    return Err(Error::new(ErrorKind::InvalidInput, format!("Frame of {} bytes exceeds {} bytes", frame.len(), MAX_FRAME_SIZE)));
+                }
+                let size = frame.len() as u32;
+                writer.write_all(&[(size >> 24) as u8, (size >> 16) as u8, (size >> 8) as u8, size as u8])?;
+                writer.write_all(frame)
+            }
+        }
+    }
+}
+
+fn read_line_frame<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut frame = Vec::new();
+    let size = reader.by_ref().take(MAX_FRAME_SIZE as u64 + 1).read_until(b'\n', &mut frame)?;
+    if size == 0 {
+        return Ok(None);
+    }
+    if frame.last() == Some(&b'\n') {
+        frame.pop();
+        if frame.last() == Some(&b'\r') {
+            frame.pop();
+        }
+    } else if size > MAX_FRAME_SIZE {
+        return Err(Error::new(ErrorKind::InvalidData, format!("Line exceeds {} bytes", MAX_FRAME_SIZE)));
+    }
+    Ok(Some(frame))
+}
+
+fn read_length_prefixed_frame<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut header: [u8; 4] = [0; 4];
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    reader.read_exact(&mut header)?;
+    let size = (header[0] as usize) << 24 | (header[1] as usize) << 16 | (header[2] as usize) << 8 | header[3] as usize;
+    if size > MAX_FRAME_SIZE {
+        return Err(Error::new(ErrorKind::InvalidData, format!("Frame of {} bytes exceeds {} bytes", size, MAX_FRAME_SIZE)));
+    }
+    let mut frame = vec![0; size];
+    reader.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_newline_frames() {
+        let mut reader = Cursor::new(b"127.0.0.1 10.0.0.1\r\n192.168.1.1\n\n172.16.0.1".to_vec());
+        assert_eq!(Some(b"127.0.0.1 10.0.0.1".to_vec()), Framing::Newline.read_frame(&mut reader).unwrap());
+        assert_eq!(Some(b"192.168.1.1".to_vec()), Framing::Newline.read_frame(&mut reader).unwrap());
+        assert_eq!(Some(b"".to_vec()), Framing::Newline.read_frame(&mut reader).unwrap());
+        assert_eq!(Some(b"172.16.0.1".to_vec()), Framing::Newline.read_frame(&mut reader).unwrap());
+        assert_eq!(None, Framing::Newline.read_frame(&mut reader).unwrap());
+    }
+
+    #[test]
+    fn test_read_length_prefixed_frames() {
+        let mut reader = Cursor::new(b"\x00\x00\x00\x0b192.168.1.1\x00\x00\x00\x00".to_vec());
+        assert_eq!(Some(b"192.168.1.1".to_vec()), Framing::LengthPrefixed.read_frame(&mut reader).unwrap());
+        assert_eq!(Some(b"".to_vec()), Framing::LengthPrefixed.read_frame(&mut reader).unwrap());
+        assert_eq!(None, Framing::LengthPrefixed.read_frame(&mut reader).unwrap());
+    }
+
+    #[test]
+    fn test_write_frames_round_trip() {
+        for framing in &[Framing::Newline, Framing::LengthPrefixed] {
+            let mut out = Vec::new();
+            framing.write_frame(&mut out, b"10.0.0.0/8").unwrap();
+            framing.write_frame(&mut out, b"").unwrap();
+            let mut reader = Cursor::new(out);
+            assert_eq!(Some(b"10.0.0.0/8".to_vec()), framing.read_frame(&mut reader).unwrap());
+            assert_eq!(Some(b"".to_vec()), framing.read_frame(&mut reader).unwrap());
+            assert_eq!(None, framing.read_frame(&mut reader).unwrap());
+        }
+        assert!(Framing::Newline.write_frame(&mut Vec::new(), b"a\nb").is_err());
+    }
+
+    #[test]
+    fn test_read_length_prefixed_frame_too_large() {
+        let mut reader = Cursor::new(b"\xff\xff\xff\xff".to_vec());
+        assert!(Framing::LengthPrefixed.read_frame(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_read_length_prefixed_frame_truncated() {
+        let mut reader = Cursor::new(b"\x00\x00\x00\x0b192.168".to_vec());
+        assert!(Framing::LengthPrefixed.read_frame(&mut reader).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+pub const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+const MAX_HEADER_LINE_SIZE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+
+/// Minimal HTTP/1.1 request; only bodies with `Content-Length` are supported.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|&&(ref key, _)| key.eq_ignore_ascii_case(name))
+            .map(|&(_, ref value)| value.as_str())
+    }
+
+    /// Media type of the body without parameters, lower-cased.
+    pub fn content_type(&self) -> Option<String> {
+        self.header("Content-Type").map(|value| {
+            value.split(';').next().unwrap_or("").trim().to_ascii_lowercase()
+        })
+    }
+
+    pub fn wants_close(&self) -> bool {
+        self.header("Connection").map_or(false, |value| value.eq_ignore_ascii_case("close"))
+    }
+}
+
+#[derive(Debug)]
+pub enum HttpError {
+    Io(io::Error),
+    /// Request can not be processed and should be answered with given status and reason.
+    Status(u16, String),
+}
+
+impl From<io::Error> for HttpError {
+    fn from(e: io::Error) -> HttpError {
+        HttpError::Io(e)
+    }
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> Result<Option<String>, HttpError> {
+    let mut line = Vec::new();
+    reader.by_ref().take(MAX_HEADER_LINE_SIZE as u64).read_until(b'\n', &mut line)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if line.last() != Some(&b'\n') {
+        return Err(HttpError::Status(431, "Header line too long or incomplete".to_owned()));
+    }
+    match String::from_utf8(line) {
+        Ok(line) => Ok(Some(line.trim_end_matches(|c| c == '\r' || c == '\n').to_owned())),
+        Err(_) => Err(HttpError::Status(400, "Header is not valid UTF-8".to_owned()))
+    }
+}
+
+/// Reads a request from `reader`. Returns `Ok(None)` when the peer closed the connection.
+pub fn read_request<R: BufRead>(reader: &mut R) -> Result<Option<Request>, HttpError> {
+    let request_line = match read_line(reader)? {
+        Some(line) => line,
+        None => return Ok(None)
+    };
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(path), Some(version)) if version.starts_with("HTTP/1.") => (method.to_owned(), path.to_owned()),
+        _ => return Err(HttpError::Status(400, format!("Malformed request line `{}`", request_line)))
+    };
+    let headers = read_headers(reader)?;
+    let mut request = Request { method, path, headers, body: Vec::new() };
+    if request.header("Transfer-Encoding").is_some() {
+        return Err(HttpError::Status(411, "Chunked bodies are not supported, send Content-Length".to_owned()));
+    }
+    let length = match request.header("Content-Length") {
+        Some(value) => match value.trim().parse::<usize>() {
+            Ok(length) => length,
+            Err(_) => return Err(HttpError::Status(400, format!("Invalid Content-Length `{}`", value)))
+        },
+        None => 0
+    };
+    if length > MAX_BODY_SIZE {
+        return Err(HttpError::Status(413, format!("Body exceeds {} bytes", MAX_BODY_SIZE)));
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body)?;
+    Ok(Some(request))
+}
+
+fn read_headers<R: BufRead>(reader: &mut R) -> Result<Vec<(String, String)>, HttpError> {
+    let mut headers = Vec::new();
+    loop {
+        let line = match read_line(reader)? {
+            Some(line) => line,
+            None => return Err(HttpError::Status(400, "Connection closed inside headers".to_owned()))
+        };
+        if line.is_empty() {
+            return Ok(headers);
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(HttpError::Status(431, "Too many headers".to_owned()));
+        }
+        match line.find(':') {
+            Some(idx) => headers.push((line[..idx].trim().to_owned(), line[idx + 1..].trim().to_owned())),
+            None => return Err(HttpError::Status(400, format!("Malformed header `{}`", line)))
+        }
+    }
+}
+
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Unknown"
+    }
+}
+
+pub fn write_response<W: Write>(writer: &mut W, status: u16, body: &str) -> io::Result<()> {
+    write!(writer, "HTTP/1.1 {} {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+           status, reason_phrase(status), body.len(), body)?;
+    writer.flush()
+}
+
+/// Target of an outgoing request; only plain `http://` URLs are supported.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Url {
+    /// `host:port` as written in the URL, used for the `Host` header.
+    pub authority: String,
+    /// Address to connect to, port 80 is added when missing.
+    pub address: String,
+    pub path: String,
+}
+
+pub fn parse_url(url: &str) -> Result<Url, String> {
+    if !url.starts_with("http://") {
+        return Err(format!("Unsupported url `{}`; Only http:// urls are supported", url));
+    }
+    let rest = &url["http://".len()..];
+    let (authority, path) = match rest.find('/') {
+        Some(idx) => (&rest[..idx], &rest[idx..]),
+        None => (rest, "/")
+    };
+    if authority.is_empty() {
+        return Err(format!("Url `{}` has no host", url));
+    }
+    let has_port = match (authority.rfind(':'), authority.rfind(']')) {
+        (Some(colon), Some(bracket)) => colon > bracket,
+        (Some(_), None) => true,
+        _ => false
+    };
+    let address = if has_port { authority.to_owned() } else { format!("{}:80", authority) };
+    Ok(Url { authority: authority.to_owned(), address, path: path.to_owned() })
+}
+
+/// Sends a POST request with `Connection: close` and returns the response status.
+pub fn post(url: &Url, headers: &[(String, String)], content_type: &str, body: &[u8], timeout: Duration) -> Result<u16, String> {
+    let address = match url.address.to_socket_addrs().map(|mut addresses| addresses.next()) {
+        Ok(Some(address)) => address,
+        Ok(None) => return Err(format!("Address `{}` did not resolve", url.address)),
+        Err(e) => return Err(format!("Can not resolve `{}`; Cause: {}", url.address, e))
+    };
+    let mut stream = match TcpStream::connect_timeout(&address, timeout) {
+        Ok(stream) => stream,
+        Err(e) => return Err(format!("Can not connect to `{}`; Cause: {}", url.address, e))
+    };
+    stream.set_read_timeout(Some(timeout)).and_then(|_| stream.set_write_timeout(Some(timeout)))
+        .map_err(|e| format!("Can not set timeouts; Cause: {}", e))?;
+    write_request(&mut stream, url, headers, content_type, body)
+        .map_err(|e| format!("Sending request to `{}` failed; Cause: {}", url.address, e))?;
+    match read_status(&mut BufReader::new(stream)) {
+        Ok(status) => Ok(status),
+        Err(HttpError::Io(e)) => Err(format!("Reading response from `{}` failed; Cause: {}", url.address, e)),
+        Err(HttpError::Status(_, reason)) => Err(format!("Invalid response from `{}`; Cause: {}", url.address, reason))
+    }
+}
+
+fn write_request<W: Write>(writer: &mut W, url: &Url, headers: &[(String, String)], content_type: &str, body: &[u8]) -> io::Result<()> {
+    let mut head = format!("POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+                           url.path, url.authority, content_type, body.len());
+    for &(ref name, ref value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    writer.write_all(head.as_bytes())?;
+    writer.write_all(body)?;
+    writer.flush()
+}
+
+fn read_status<R: BufRead>(reader: &mut R) -> Result<u16, HttpError> {
+    let status_line = match read_line(reader)? {
+        Some(line) => line,
+        None => return Err(HttpError::Status(400, "Connection closed before response".to_owned()))
+    };
+    let mut parts = status_line.split_whitespace();
+    match (parts.next(), parts.next().map(|code| code.parse::<u16>())) {
+        (Some(version), Some(Ok(status))) if version.starts_with("HTTP/1.") => {
+            read_headers(reader)?;
+            Ok(status)
+        }
+        _ => Err(HttpError::Status(400, format!("Malformed status line `{}`", status_line)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_request() {
+        let mut reader = Cursor::new(b"POST /ingest HTTP/1.1\r\nHost: localhost\r\ncontent-type: text/plain; charset=utf-8\r\nContent-Length: 9\r\n\r\n127.0.0.1".to_vec());
+        let request = read_request(&mut reader).unwrap().unwrap();
+        assert_eq!("POST", request.method);
+        assert_eq!("/ingest", request.path);
+        assert_eq!(Some("text/plain".to_owned()), request.content_type());
+        assert_eq!(b"127.0.0.1".to_vec(), request.body);
+        assert!(read_request(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_request_malformed() {
+        let mut reader = Cursor::new(b"garbage\r\n\r\n".to_vec());
+        match read_request(&mut reader) {
+            Err(HttpError::Status(400, _)) => {}
+            other => panic!("Unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_read_request_too_large() {
+        let mut reader = Cursor::new(b"POST / HTTP/1.1\r\nContent-Length: 999999999\r\n\r\n".to_vec());
+        match read_request(&mut reader) {
+            Err(HttpError::Status(413, _)) => {}
+            other => panic!("Unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(Url { authority: "localhost:8080".to_owned(), address: "localhost:8080".to_owned(), path: "/hook?x=1".to_owned() },
+                   parse_url("http://localhost:8080/hook?x=1").unwrap());
+        assert_eq!(Url { authority: "[::1]".to_owned(), address: "[::1]:80".to_owned(), path: "/".to_owned() },
+                   parse_url("http://[::1]").unwrap());
+        assert!(parse_url("https://localhost/").is_err());
+        assert!(parse_url("http:///hook").is_err());
+    }
+
+    #[test]
+    fn test_write_request_read_status() {
+        let url = parse_url("http://localhost:8080/hook").unwrap();
+        let mut out = Vec::new();
+        write_request(&mut out, &url, &[("X-Token".to_owned(), "secret".to_owned())], "text/plain", b"10.0.0.0/8").unwrap();
+        let request = read_request(&mut Cursor::new(out)).unwrap().unwrap();
+        assert_eq!("/hook", request.path);
+        assert_eq!(Some("secret"), request.header("x-token"));
+        assert_eq!(b"10.0.0.0/8".to_vec(), request.body);
+
+        let mut response = Vec::new();
+        write_response(&mut response, 202, "accepted").unwrap();
+        assert_eq!(202, read_status(&mut Cursor::new(response)).unwrap());
+    }
+
+    #[test]
+    fn test_write_response() {
+        let mut out = Vec::new();
+        write_response(&mut out, 202, "accepted 2").unwrap();
+        assert_eq!(b"HTTP/1.1 202 Accepted\r\nContent-Type: text/plain\r\nContent-Length: 10\r\n\r\naccepted 2".to_vec(), out);
+    }
+}
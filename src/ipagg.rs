@@ -1,53 +1,208 @@
-use formatters::{simple_formatter};
-use listeners::{listener_factory, get_credentials_from_settings};
-use parsers::{nom_ip_parser};
+use config::{reload_settings, Receivers, Senders, Settings};
+use dump::{DumpEvent, DumpInfo};
+use listeners::{listener_factory, get_credentials, IpSender, StopHook};
+use parsers::parser_by_name;
+use signals::{reload_requested, shutdown_requested};
 use SETTINGS;
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, sync_channel, Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError};
 use std::thread;
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use subnet_tree::IPTree;
-use senders::{create_publisher, get_publisher_credentials};
+use senders::{create_publisher, formatter_from_settings, get_publisher_credentials};
+
+const DUMP_CHUNK_SIZE: usize = 1000;
+const SIGNAL_POLL_INTERVAL_MS: u64 = 100;
+
+/// Name of a running sink and queue of its thread.
+type Sink = (String, SyncSender<DumpEvent>);
+
+/// Cleared once the thread holding it ends, even by panic.
+struct Running(Arc<AtomicBool>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
 
 pub struct IpAggregator {
+    /// Settings in effect; starts as `SETTINGS` and is replaced by every successful reload.
+    settings: Settings,
+    /// Tree, fan-out and sink threads; all of them finish once the tree thread terminates.
     handles: Vec<JoinHandle<()>>,
+    /// Running listener threads with hooks stopping them.
+    listeners: Vec<(StopHook, JoinHandle<()>)>,
+    /// Sinks with settings they were started with and whether their thread still runs.
+    sinks: Vec<(Senders, Sink, Arc<AtomicBool>)>,
+    sinks_tx: Option<Sender<Vec<Sink>>>,
+    timer_tx: Option<Sender<Duration>>,
 }
 
 
 impl IpAggregator {
     pub fn new() -> IpAggregator {
         IpAggregator {
+            settings: SETTINGS.clone(),
             handles: Vec::new(),
+            listeners: Vec::new(),
+            sinks: Vec::new(),
+            sinks_tx: None,
+            timer_tx: None,
         }
     }
 
-    pub fn start(&mut self) -> () {
-        let (octet_tx, octet_rx) = channel();
+    /// Runs the aggregator until shutdown is requested by a signal, then performs the final dump
+    /// and waits for publishers to flush it. Config is reloaded on request meanwhile.
+    pub fn start(&mut self) -> Result<(), String> {
+        let (octet_tx, octet_rx) = sync_channel(self.settings.get_event_queue_size());
         let timer_tx = octet_tx.clone();
+        let terminate_tx = octet_tx.clone();
         let (cidr_tx, cidr_rx) = channel();
-        self.start_listener_thread(octet_tx);
+        for receiver in self.settings.get_receivers() {
+            self.start_listener_thread(receiver, octet_tx.clone());
+        }
+        drop(octet_tx);
         self.start_tree_event_listener(octet_rx, cidr_tx);
         self.start_dump_timer(timer_tx);
         self.start_push_result_thread(cidr_rx);
 
-        while !self.handles.is_empty() {
-            let handle = self.handles.pop().unwrap();
-            handle.join().unwrap();
+        while !shutdown_requested() {
+            thread::sleep(Duration::from_millis(SIGNAL_POLL_INTERVAL_MS));
+            if reload_requested() {
+                info!("Reload requested; Reading settings");
+                if let Err(e) = self.reload() {
+                    error!("Settings were not reloaded, keeping current ones; Cause: {}", e);
+                }
+            }
         }
+        info!("Shutdown requested; Performing final dump");
+        self.shutdown(terminate_tx)
     }
 
-    fn start_listener_thread(&mut self, sender: Sender<AggEvent>) {
-        self.handles.push(thread::spawn(move || {
-            let credentials = match get_credentials_from_settings(&SETTINGS) {
+    /// Applies re-read settings which are safe to change live: dump timer, shutdown timeout and
+    /// sinks with their formatters. Sinks with unchanged settings keep running. Other changes are
+    /// only logged, they need a restart.
+    fn reload(&mut self) -> Result<(), String> {
+        let settings = reload_settings(&self.settings)?;
+        for sink in settings.get_senders() {
+            get_publisher_credentials(&sink)?;
+        }
+        for name in SETTINGS.restart_required_changes(&settings) {
+            warn!("Change of `{}` needs restart; Keeping value it was started with", name);
+        }
+        info!("Filters and exclusion lists are not supported; Nothing to reload for them");
+        if settings.get_publish_timer() != self.settings.get_publish_timer() {
+            info!("Publish timer changed to {} seconds", settings.get_publish_timer());
+            if let Some(ref timer) = self.timer_tx {
+                timer.send(Duration::from_secs(settings.get_publish_timer() as u64)).ok();
+            }
+        }
+        let stopped_sinks = self.sinks.iter().any(|&(_, _, ref running)| !running.load(Ordering::SeqCst));
+        if stopped_sinks || settings.get_senders() != self.settings.get_senders() {
+            self.replace_sinks(settings.get_senders());
+        }
+        self.settings = settings;
+        Ok(())
+    }
+
+    /// Starts sinks for new settings and restarts stopped ones, then hands the resulting list to
+    /// the fan-out thread, which drops the removed sinks.
+    fn replace_sinks(&mut self, senders: Vec<Senders>) {
+        let mut previous = mem::replace(&mut self.sinks, Vec::new());
+        for (idx, settings) in senders.into_iter().enumerate() {
+            let reusable = previous.iter().position(|&(ref started_with, _, ref running)| {
+                *started_with == settings && running.load(Ordering::SeqCst)
+            });
+            match reusable {
+                Some(position) => self.sinks.push(previous.remove(position)),
+                None => {
+                    let sink = self.start_sink(idx, settings);
+                    info!("Sink `{}` started", (sink.1).0);
+                    self.sinks.push(sink);
+                }
+            }
+        }
+        for (_, (name, _), running) in previous {
+            if running.load(Ordering::SeqCst) {
+                info!("Sink `{}` removed", name);
+            } else {
+                info!("Stopped sink `{}` removed", name);
+            }
+        }
+        if let Some(ref sinks_tx) = self.sinks_tx {
+            sinks_tx.send(self.sinks.iter().map(|&(_, ref sink, _)| sink.clone()).collect()).ok();
+        }
+    }
+
+    /// Stops listeners, terminates the tree thread once they are finished and waits at most
+    /// `shutdown_timeout` for all of it, including publishing of the final dump.
+    fn shutdown(&mut self, sender: SyncSender<AggEvent>) -> Result<(), String> {
+        let timeout = self.settings.get_shutdown_timeout();
+        // Sink threads finish only after every queue handle is dropped.
+        self.sinks.clear();
+        self.sinks_tx = None;
+        let mut listeners = Vec::new();
+        for (stop, handle) in mem::replace(&mut self.listeners, Vec::new()) {
+            stop();
+            listeners.push(handle);
+        }
+        let handles = mem::replace(&mut self.handles, Vec::new());
+        let (done_tx, done_rx) = channel();
+        thread::spawn(move || {
+            let failed = listeners.into_iter().map(JoinHandle::join).filter(Result::is_err).count();
+            if failed > 0 {
+                warn!("{} listeners failed before shutdown", failed);
+            }
+            info!("Listeners stopped");
+            let result = match sender.send(AggEvent::TERMINATE) {
+                Ok(()) => {
+                    let panicked = handles.into_iter().map(JoinHandle::join).filter(Result::is_err).count();
+                    if panicked == 0 {
+                        Ok(())
+                    } else {
+                        Err(format!("{} aggregator threads panicked", panicked))
+                    }
+                }
+                Err(_) => Err("IPTree thread is not running, final dump is lost".to_owned())
+            };
+            done_tx.send(result).ok();
+        });
+        match done_rx.recv_timeout(Duration::from_secs(timeout as u64)) {
+            Ok(result) => result,
+            Err(_) => Err(format!("Publishers didn't finish within {} seconds", timeout))
+        }
+    }
+
+    /// Waits until the listener is created, so that its stop hook can be kept.
+    fn start_listener_thread(&mut self, settings: Receivers, sender: SyncSender<AggEvent>) {
+        let (stop_tx, stop_rx) = channel();
+        let handle = thread::spawn(move || {
+            let credentials = match get_credentials(&settings) {
                 Ok(creds) => creds,
                 Err(e) => {
                     error!("Could not get valid credentials; Cause: {}", e);
                     panic!();
                 }
             };
-            match listener_factory(credentials, nom_ip_parser, sender) {
+            let parser = match parser_by_name(settings.get_parser()) {
+                Ok(parser) => parser,
+                Err(e) => {
+                    error!("Could not get parser; Cause: {}", e);
+                    panic!();
+                }
+            };
+            match listener_factory(credentials, parser, IpSender::new(sender, settings.get_tag())) {
                 Ok(ref mut listener) => {
+                    stop_tx.send(listener.stop_hook()).ok();
                     match listener.listen() {
+                        Err(ref e) if shutdown_requested() => {
+                            info!("Listener stopped on shutdown; Cause: {}", e);
+                        },
                         Err(e) => {
                             error!("Listener stopped listening; Cause: {}", e);
                             panic!();
@@ -60,31 +215,42 @@ impl IpAggregator {
                     panic!();
                 }
             }
-        }));
+        });
+        // failed listener thread drops the hook sender without sending
+        if let Ok(stop) = stop_rx.recv() {
+            self.listeners.push((stop, handle));
+        }
     }
 
-    fn start_tree_event_listener(&mut self, receiver: Receiver<AggEvent>, sender: Sender<Vec<(u32, u8)>>) {
+    fn start_tree_event_listener(&mut self, receiver: Receiver<AggEvent>, sender: Sender<DumpEvent>) {
+        let drain_limit = self.settings.get_event_queue_size();
+        let tree_options = self.settings.get_tree_options();
         self.handles.push(thread::spawn(move || {
-            let mut tree = IPTree::new();
+            let mut tree = IPTree::with_options(tree_options);
+            let mut sequence: u64 = 0;
+            let mut window_start = unix_timestamp();
+            let mut source_stats: HashMap<String, u64> = HashMap::new();
             loop {
                 match receiver.recv() {
                     Ok(event) => {
                         match event {
-                            AggEvent::ADD(data) => {
-                                data.into_iter().for_each(|octet| {tree.add(&octet)});
+                            AggEvent::ADD(data, source) => {
+                                add_addresses(&mut tree, &mut source_stats, data, source);
                             },
                             AggEvent::DUMP => {
-                                let mut ip_tree_iter = tree.walk();
-                                loop {
-                                    let ipvec: Vec<(u32, u8)> = (&mut ip_tree_iter).take(1000).collect();
-                                    let vec_len = ipvec.len();
-                                    sender.send(ipvec).unwrap();
-                                    if vec_len < 1000 {
-                                        break;
-                                    }
-                                }
+                                sequence += 1;
+                                window_start = send_dump(&tree, &mut source_stats, sequence, window_start, &sender);
                             },
                             AggEvent::TERMINATE => {
+                                // Listeners may keep the queue busy; take at most one queue worth of events.
+                                for event in receiver.try_iter().take(drain_limit) {
+                                    if let AggEvent::ADD(data, source) = event {
+                                        add_addresses(&mut tree, &mut source_stats, data, source);
+                                    }
+                                }
+                                sequence += 1;
+                                send_dump(&tree, &mut source_stats, sequence, window_start, &sender);
+                                info!("Final dump {} sent to publishers", sequence);
                                 drop(sender);
                                 break;
                             }
@@ -99,33 +265,222 @@ impl IpAggregator {
         }));
     }
 
-    fn start_dump_timer(&mut self, sender: Sender<AggEvent>) {
-        let sleep_dur = Duration::from_secs(SETTINGS.get_publish_timer() as u64);
-        self.handles.push(thread::spawn(move || {
+    /// Requests a dump every `publish_timer` seconds. New period received from the main thread
+    /// restarts the wait.
+    fn start_dump_timer(&mut self, sender: SyncSender<AggEvent>) {
+        let mut sleep_dur = Duration::from_secs(self.settings.get_publish_timer() as u64);
+        let (period_tx, period_rx) = channel();
+        self.timer_tx = Some(period_tx);
+        thread::spawn(move || {
             loop {
-                thread::sleep(sleep_dur);
-                sender.send(AggEvent::DUMP).unwrap();
+                match period_rx.recv_timeout(sleep_dur) {
+                    Ok(period) => sleep_dur = period,
+                    Err(RecvTimeoutError::Timeout) => {
+                        if sender.send(AggEvent::DUMP).is_err() {
+                            break;
+                        }
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break
+                }
             }
+        });
+    }
+
+    fn start_push_result_thread(&mut self, receiver: Receiver<DumpEvent>) {
+        for (idx, settings) in self.settings.get_senders().into_iter().enumerate() {
+            let sink = self.start_sink(idx, settings);
+            self.sinks.push(sink);
+        }
+        let sinks: Vec<Sink> = self.sinks.iter().map(|&(_, ref sink, _)| sink.clone()).collect();
+        let (sinks_tx, sinks_rx) = channel();
+        self.sinks_tx = Some(sinks_tx);
+        self.handles.push(thread::spawn(move || {
+            fan_out(receiver, sinks, sinks_rx);
         }));
     }
 
-    fn start_push_result_thread(&mut self, receiver: Receiver<Vec<(u32, u8)>>) {
+    fn start_sink(&mut self, idx: usize, settings: Senders) -> (Senders, Sink, Arc<AtomicBool>) {
+        let name = format!("{}#{}", settings.get_publisher_type(), idx);
+        let (sink_tx, sink_rx) = sync_channel(settings.get_queue_size());
+        let running = Arc::new(AtomicBool::new(true));
+        self.start_sink_thread(settings.clone(), sink_rx, Running(running.clone()));
+        (settings, (name, sink_tx), running)
+    }
+
+    fn start_sink_thread(&mut self, settings: Senders, receiver: Receiver<DumpEvent>, running: Running) {
         self.handles.push(thread::spawn(move || {
-            let creds = match get_publisher_credentials(&SETTINGS) {
+            let _running = running;
+            let creds = match get_publisher_credentials(&settings) {
                 Ok(creds) => creds,
                 Err(e) => {
                     error!("Couldn't obtain publisher settings; Cause: {}", e);
                     panic!();
                 }
             };
-            let mut sender = create_publisher(creds, simple_formatter, receiver).unwrap();
+            let formatter = match formatter_from_settings(&settings) {
+                Ok(formatter) => formatter,
+                Err(e) => {
+                    error!("Couldn't obtain formatter; Cause: {}", e);
+                    panic!();
+                }
+            };
+            let mut sender = create_publisher(creds, formatter, receiver).unwrap();
             sender.run_sender();
         }));
     }
 }
 
 pub enum AggEvent {
-    ADD(Vec<[u8;4]>),
+    /// Parsed addresses and tag of the listener they came from.
+    ADD(Vec<[u8;4]>, Option<String>),
     DUMP,
     TERMINATE,
 }
+
+/// Fans every dump event out to all sinks, each running on its own thread behind a bounded
+/// queue. Fan-out never blocks on a sink: a sink whose queue is full when a dump begins skips the
+/// whole dump, and a sink whose queue fills up during a dump gets nothing more of it, so it sees
+/// the dump without end marker. A failed sink is dropped. Sink list replaced on reload takes
+/// effect at the next dump.
+fn fan_out(receiver: Receiver<DumpEvent>, sinks: Vec<Sink>, sinks_rx: Receiver<Vec<Sink>>) {
+    // Sinks with whether they take the current dump.
+    let mut sinks: Vec<(Sink, bool)> = sinks.into_iter().map(|sink| (sink, false)).collect();
+    let mut sequence = 0;
+    for event in receiver.iter() {
+        if let DumpEvent::Begin(ref info) = event {
+            if let Some(replaced) = sinks_rx.try_iter().last() {
+                sinks = replaced.into_iter().map(|sink| (sink, false)).collect();
+            }
+            sequence = info.sequence;
+            sinks.iter_mut().for_each(|sink| sink.1 = true);
+        }
+        sinks = sinks.into_iter().filter_map(|((name, sink), taking)| {
+            if !taking {
+                return Some(((name, sink), false));
+            }
+            match sink.try_send(event.clone()) {
+                Ok(()) => Some(((name, sink), true)),
+                Err(TrySendError::Full(_)) => {
+                    match event {
+                        DumpEvent::Begin(_) => warn!("Sink `{}` is falling behind; Skipping dump {}", name, sequence),
+                        _ => warn!("Sink `{}` is falling behind; Dropping rest of dump {}", name, sequence)
+                    }
+                    Some(((name, sink), false))
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    error!("Sink `{}` stopped; Removing it from publishers", name);
+                    None
+                }
+            }
+        }).collect();
+    }
+}
+
+fn add_addresses(tree: &mut IPTree, source_stats: &mut HashMap<String, u64>, data: Vec<[u8; 4]>, source: Option<String>) {
+    if let Some(source) = source {
+        *source_stats.entry(source).or_insert(0) += data.len() as u64;
+    }
+    data.into_iter().for_each(|octet| {tree.add(&octet)});
+}
+
+/// Streams the whole tree framed by dump markers; returns timestamp of the dump, which starts
+/// the next window.
+fn send_dump(tree: &IPTree, source_stats: &mut HashMap<String, u64>, sequence: u64, window_start: u64,
+             sender: &Sender<DumpEvent>) -> u64 {
+    for (source, count) in source_stats.drain() {
+        info!("Source `{}` sent {} addresses since last dump", source, count);
+    }
+    let mut cidrs: Vec<(u32, u8)> = tree.walk().collect();
+    cidrs.sort();
+    let timestamp = unix_timestamp();
+    let info = DumpInfo { sequence, window_start, timestamp, total: cidrs.len() };
+    sender.send(DumpEvent::Begin(info)).unwrap();
+    for (idx, chunk) in cidrs.chunks(DUMP_CHUNK_SIZE).enumerate() {
+        sender.send(DumpEvent::Chunk(idx * DUMP_CHUNK_SIZE, chunk.to_vec())).unwrap();
+    }
+    sender.send(DumpEvent::End(info)).unwrap();
+    timestamp
+}
+
+fn unix_timestamp() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_secs(),
+        Err(_) => 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dump(sequence: u64, chunks: usize) -> Vec<DumpEvent> {
+        let info = DumpInfo { sequence, window_start: 0, timestamp: 1, total: chunks };
+        let mut events = vec![DumpEvent::Begin(info)];
+        events.extend((0..chunks).map(|idx| DumpEvent::Chunk(idx, vec![(idx as u32, 32)])));
+        events.push(DumpEvent::End(info));
+        events
+    }
+
+    #[test]
+    fn test_running_is_cleared_when_thread_panics() {
+        let running = Arc::new(AtomicBool::new(true));
+        let guard = Running(running.clone());
+        assert!(thread::spawn(move || {
+            let _guard = guard;
+            panic!("sink failed");
+        }).join().is_err());
+        assert!(!running.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_fan_out_drops_rest_of_dump_for_stalled_sink() {
+        let (event_tx, event_rx) = channel();
+        let (stalled_tx, stalled_rx) = sync_channel(3);
+        let (observer_tx, observer_rx) = sync_channel(16);
+        let (_sinks_tx, sinks_rx) = channel();
+        let sinks = vec![("stalled#0".to_owned(), stalled_tx), ("observer#1".to_owned(), observer_tx)];
+        let fan_out_handle = thread::spawn(move || fan_out(event_rx, sinks, sinks_rx));
+        for event in dump(1, 5) {
+            event_tx.send(event).unwrap();
+        }
+        // Observer gets the whole dump although the stalled sink is not read meanwhile.
+        assert_eq!(dump(1, 5), observer_rx.iter().take(7).collect::<Vec<_>>());
+        assert_eq!(dump(1, 5)[..3].to_vec(), stalled_rx.try_iter().collect::<Vec<_>>());
+        for event in dump(2, 1) {
+            event_tx.send(event).unwrap();
+        }
+        drop(event_tx);
+
+        let received: Vec<DumpEvent> = stalled_rx.iter().collect();
+        fan_out_handle.join().unwrap();
+        assert_eq!(dump(2, 1), received);
+    }
+
+    #[test]
+    fn test_fan_out_skips_whole_dump_when_sink_is_full() {
+        let (event_tx, event_rx) = channel();
+        let (full_tx, full_rx) = sync_channel(5);
+        let (observer_tx, observer_rx) = sync_channel(16);
+        let (_sinks_tx, sinks_rx) = channel();
+        let stale = DumpEvent::Chunk(0, Vec::new());
+        for _ in 0..5 {
+            full_tx.send(stale.clone()).unwrap();
+        }
+        let sinks = vec![("full#0".to_owned(), full_tx), ("observer#1".to_owned(), observer_tx)];
+        let fan_out_handle = thread::spawn(move || fan_out(event_rx, sinks, sinks_rx));
+        for event in dump(1, 3) {
+            event_tx.send(event).unwrap();
+        }
+        // Observer gets events after the full sink, so the whole dump 1 was handled once it has them.
+        assert_eq!(dump(1, 3), observer_rx.iter().take(5).collect::<Vec<_>>());
+        assert_eq!(vec![stale; 5], full_rx.iter().take(5).collect::<Vec<_>>());
+        for event in dump(2, 3) {
+            event_tx.send(event).unwrap();
+        }
+        drop(event_tx);
+
+        let received: Vec<DumpEvent> = full_rx.iter().collect();
+        fan_out_handle.join().unwrap();
+        assert_eq!(dump(2, 3), received);
+    }
+}
@@ -0,0 +1,14 @@
+//! Aggregation engine of ipaggregator: `subnet_tree::IPTree` merges IPv4 addresses into the
+//! smallest set of CIDRs, `parsers` turn raw input into addresses and `formatters` render
+//! aggregated dumps for consumers.
+#[macro_use]
+extern crate serde_derive;
+extern crate serde;
+extern crate serde_json;
+#[macro_use]
+extern crate nom;
+
+pub mod dump;
+pub mod formatters;
+pub mod parsers;
+pub mod subnet_tree;
@@ -1,21 +1,130 @@
 use parsers::StreamParser;
-use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{SendError, SyncSender, TrySendError};
 use ipagg::AggEvent;
-use config::Settings;
+use config::Receivers;
+use framing::Framing;
+use std::io::{BufReader, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::time::Duration;
+use kafka::client::FetchOffset;
 
-pub type IpSender = Sender<AggEvent>;
+/// Connections served at once by a stream listener, each one on its own thread.
+const MAX_CONNECTIONS: usize = 64;
+/// Connections idle for longer are closed.
+const READ_TIMEOUT_SECS: u64 = 30;
+
+/// Event queue handle of a single listener; every batch it sends is tagged with the listener's source.
+#[derive(Clone)]
+pub struct IpSender {
+    sender: SyncSender<AggEvent>,
+    source: Option<String>,
+}
+
+impl IpSender {
+    pub fn new(sender: SyncSender<AggEvent>, source: Option<String>) -> IpSender {
+        IpSender { sender, source }
+    }
+
+    /// Blocks while the event queue is full.
+    pub fn send(&self, data: Vec<[u8; 4]>) -> Result<(), SendError<AggEvent>> {
+        self.sender.send(AggEvent::ADD(data, self.source.clone()))
+    }
+
+    pub fn try_send(&self, data: Vec<[u8; 4]>) -> Result<(), TrySendError<AggEvent>> {
+        self.sender.try_send(AggEvent::ADD(data, self.source.clone()))
+    }
+}
 
 pub trait Listener {
     fn listen(&mut self) -> Result<(), String>;
+
+    /// Hook which makes a running `listen` return `Ok(())`; called from another thread on shutdown.
+    fn stop_hook(&self) -> StopHook;
+}
+
+pub type StopHook = Box<Fn() + Send>;
+
+/// Set by the stop hook of a listener and checked by its `listen` loop.
+#[derive(Clone, Default)]
+pub struct StopFlag(Arc<AtomicBool>);
+
+impl StopFlag {
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Address a blocked socket bound to `address` can be woken up through.
+fn wake_address(address: SocketAddr) -> SocketAddr {
+    match address.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), address.port()),
+        IpAddr::V6(ip) if ip.is_unspecified() => SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)), address.port()),
+        _ => address
+    }
+}
+
+/// Bounds the number of connections a listener serves at once.
+struct ConnectionLimit {
+    open: Arc<AtomicUsize>,
+    max: usize,
+}
+
+impl ConnectionLimit {
+    fn new(max: usize) -> ConnectionLimit {
+        ConnectionLimit { open: Arc::new(AtomicUsize::new(0)), max }
+    }
+
+    /// Takes a place for a new connection; `None` when `max` connections are open.
+    fn acquire(&self) -> Option<ConnectionSlot> {
+        if self.open.fetch_add(1, Ordering::SeqCst) >= self.max {
+            self.open.fetch_sub(1, Ordering::SeqCst);
+            None
+        } else {
+            Some(ConnectionSlot(self.open.clone()))
+        }
+    }
+}
+
+/// Taken by every served connection; releases its place when the connection ends.
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Stop hook of a listener accepting connections on `address`; connects to it to wake up `accept`.
+fn tcp_stop_hook(stopped: &StopFlag, address: Option<SocketAddr>) -> StopHook {
+    let stopped = stopped.clone();
+    Box::new(move || {
+        stopped.stop();
+        if let Some(address) = address {
+            if let Err(e) = TcpStream::connect(wake_address(address)) {
+                warn!("Can not wake up listener on `{}`; Cause: {}", address, e);
+            }
+        }
+    })
 }
 
 
 pub enum ListenerCredentials {
-    Kafka(Vec<String>, String, String),
+    Kafka(Vec<String>, String, String, Option<Vec<i32>>, FetchOffset),
     UdpServer(String),
+    TcpServer(String, Framing),
+    UnixStream(String, Option<u32>, Framing),
+    UnixDatagram(String, Option<u32>),
+    Tail(Vec<String>, Option<String>, Duration),
+    Http(String),
 }
 
-pub fn get_credentials_from_settings(settings: &Settings) -> Result<ListenerCredentials, String> {
+pub fn get_credentials(settings: &Receivers) -> Result<ListenerCredentials, String> {
     match settings.get_receiver_type() {
         "udp" => {
             match settings.get_udp_bind_address() {
@@ -23,11 +132,47 @@ pub fn get_credentials_from_settings(settings: &Settings) -> Result<ListenerCred
                 None => Err("Expected udp receiver, but no address to bind specified".to_owned())
             }
         }
+        "tcp" => {
+            let framing = Framing::from_name(settings.get_tcp_framing())?;
+            match settings.get_tcp_bind_address() {
+                Some(ref address) => Ok(ListenerCredentials::TcpServer(address.to_owned(), framing)),
+                None => Err("Expected tcp receiver, but no address to bind specified".to_owned())
+            }
+        }
+        "http" => {
+            match settings.get_http_bind_address() {
+                Some(ref address) => Ok(ListenerCredentials::Http(address.to_owned())),
+                None => Err("Expected http receiver, but no address to bind specified".to_owned())
+            }
+        }
+        "unix" => {
+            match settings.get_unix_receiver() {
+                Some(ref unix) => {
+                    let permissions = unix.get_permissions()?;
+                    match unix.get_mode() {
+                        "stream" => Ok(ListenerCredentials::UnixStream(unix.get_path(), permissions,
+                                                                       Framing::from_name(unix.get_framing())?)),
+                        "datagram" => Ok(ListenerCredentials::UnixDatagram(unix.get_path(), permissions)),
+                        mode => Err(format!("Unknown unix socket mode `{}` specified!", mode))
+                    }
+                }
+                None => Err("Expected unix receiver, but no unix socket settings specified".to_owned())
+            }
+        }
+        "tail" => {
+            match settings.get_tail_receiver() {
+                Some(ref tail) => Ok(ListenerCredentials::Tail(tail.get_paths(), tail.get_state_file(),
+                                                               Duration::from_millis(tail.get_poll_interval_ms()))),
+                None => Err("Expected tail receiver, but no tail settings specified".to_owned())
+            }
+        }
         "kafka" => {
             match settings.get_kafka_receiver_credentials() {
                 Some(ref kafka) => Ok(ListenerCredentials::Kafka(kafka.get_hosts(),
                                                                  kafka.get_topic(),
-                                                                 kafka.get_group())),
+                                                                 kafka.get_group(),
+                                                                 kafka.get_partitions(),
+                                                                 kafka::fetch_offset_by_name(kafka.get_fallback_offset())?)),
                 None => Err("Expected kafka receiver, but no kafka settings specified".to_owned())
             }
         }
@@ -39,8 +184,8 @@ pub fn listener_factory(creds: ListenerCredentials, parser: StreamParser, sender
                         -> Result<Box<Listener + 'static>, String>
 {
     match creds {
-        ListenerCredentials::Kafka(hosts, topic, group) => {
-            match kafka::KafkaListener::new(hosts, topic, group, parser, sender) {
+        ListenerCredentials::Kafka(hosts, topic, group, partitions, fallback_offset) => {
+            match kafka::KafkaListener::new(hosts, topic, group, partitions, fallback_offset, parser, sender) {
                 Ok(listener) => Ok(Box::new(listener)),
                 Err(e) => Err(e)
             }
@@ -51,29 +196,119 @@ pub fn listener_factory(creds: ListenerCredentials, parser: StreamParser, sender
                 Err(e) => Err(e)
             }
         }
+        ListenerCredentials::TcpServer(host, framing) => {
+            match tcp::TcpServer::new(host.as_str(), framing, parser, sender) {
+                Ok(listener) => Ok(Box::new(listener)),
+                Err(e) => Err(e)
+            }
+        }
+        ListenerCredentials::UnixStream(path, permissions, framing) => {
+            match unix::UnixStreamServer::new(path.as_str(), permissions, framing, parser, sender) {
+                Ok(listener) => Ok(Box::new(listener)),
+                Err(e) => Err(e)
+            }
+        }
+        ListenerCredentials::UnixDatagram(path, permissions) => {
+            match unix::UnixDatagramServer::new(path.as_str(), permissions, parser, sender) {
+                Ok(listener) => Ok(Box::new(listener)),
+                Err(e) => Err(e)
+            }
+        }
+        ListenerCredentials::Http(host) => {
+            match http::HttpServer::new(host.as_str(), parser, sender) {
+                Ok(listener) => Ok(Box::new(listener)),
+                Err(e) => Err(e)
+            }
+        }
+        ListenerCredentials::Tail(paths, state_file, poll_interval) => {
+            match tail::FileTailer::new(paths, state_file, poll_interval, parser, sender) {
+                Ok(listener) => Ok(Box::new(listener)),
+                Err(e) => Err(e)
+            }
+        }
+    }
+}
+
+/// Feeds frames read from a connected stream to the event queue until the peer hangs up.
+fn handle_framed_stream<R: Read>(stream: R, peer: &str, framing: Framing, parser: StreamParser, sender: IpSender) {
+    let mut reader = BufReader::new(stream);
+    loop {
+        let frame = match framing.read_frame(&mut reader) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Closing connection from {}; Cause: {}", peer, e);
+                return;
+            }
+        };
+        if frame.is_empty() {
+            continue;
+        }
+        let data = match parser(&frame) {
+            Ok(ips) => ips,
+            Err(e) => {
+                warn!("Parsing of message {:?} from {} failed. Skipping ...; Cause: {}", frame, peer, e);
+                continue;
+            }
+        };
+        if let Err(e) = sender.send(data) {
+            error!("Can not send Aggregator event via event queue; Cause: {}", e);
+            return;
+        }
     }
 }
 
 pub mod kafka {
+    use kafka::client::FetchOffset;
     use kafka::consumer::{Consumer, GroupOffsetStorage};
-    use super::{IpSender, Listener, StreamParser, AggEvent};
+    use std::thread;
+    use std::time::Duration;
+    use super::{IpSender, Listener, StopFlag, StopHook, StreamParser};
+
+    const POLL_RETRY_DELAY: Duration = Duration::from_secs(1);
 
+    pub fn fetch_offset_by_name(name: &str) -> Result<FetchOffset, String> {
+        match name {
+            "earliest" => Ok(FetchOffset::Earliest),
+            "latest" => Ok(FetchOffset::Latest),
+            other => Err(format!("Unknown kafka fallback offset `{}` specified!", other))
+        }
+    }
+
+    /// Consumes the topic until an error occurs or it is stopped. Offsets of the group are
+    /// committed once every message of a poll has been handed to the aggregator queue.
     pub struct KafkaListener {
         consumer: Consumer,
         value_parser: StreamParser,
         sender: IpSender,
+        commit_offsets: bool,
+        stopped: StopFlag,
     }
 
 
     impl KafkaListener {
-        pub fn new(hosts: Vec<String>, topic: String, group: String,
-                   value_parser: StreamParser, sender: IpSender) -> Result<KafkaListener, String> {
-            match Consumer::from_hosts(hosts)
-                .with_topic_partitions(topic, &[0, 1])
+        pub fn new(hosts: Vec<String>, topic: String, group: String, partitions: Option<Vec<i32>>,
+                   fallback_offset: FetchOffset, value_parser: StreamParser, sender: IpSender)
+                   -> Result<KafkaListener, String> {
+            let commit_offsets = !group.is_empty();
+            let builder = match partitions {
+                Some(ref partitions) => Consumer::from_hosts(hosts).with_topic_partitions(topic, partitions),
+                None => Consumer::from_hosts(hosts).with_topic(topic)
+            };
+            match builder
                 .with_group(group)
+                .with_fallback_offset(fallback_offset)
                 .with_offset_storage(GroupOffsetStorage::Kafka)
                 .create() {
-                Ok(consumer) => Ok(KafkaListener {consumer, value_parser, sender}),
+                Ok(consumer) => {
+                    for (topic, partitions) in consumer.subscriptions() {
+                        info!("Kafka consumer subscribed to `{}` partitions {:?}", topic, partitions);
+                    }
+                    if !commit_offsets {
+                        warn!("Kafka consumer has no group; Offsets will not be committed");
+                    }
+                    Ok(KafkaListener {consumer, value_parser, sender, commit_offsets, stopped: StopFlag::default()})
+                }
                 Err(e) => Err(format!("Kafka couldn't create consumer; Cause: {}", e))
             }
         }
@@ -81,36 +316,78 @@ pub mod kafka {
 
     impl Listener for KafkaListener {
         fn listen(&mut self) -> Result<(), String> {
-            for ms in self.consumer.poll().unwrap().iter() {
-                for m in ms.messages() {
-                    let messages: Vec<[u8; 4]> = match (self.value_parser)(m.value) {
-                        Ok(msg_vec) => msg_vec,
-                        Err(e) => {
-                            warn!("Parsing of message `{:?}` failed; Cause: {}. Skipping ...", m.value, e);
-                            continue;
-                        }
-                    };
-                    match self.sender.send(AggEvent::ADD(messages)) {
-                        Ok(()) => {},
-                        Err(e) => {
-                            return Err(format!("Can not send Aggregator event via event queue; Cause: {}", e))
+            while !self.stopped.is_stopped() {
+                let message_sets = match self.consumer.poll() {
+                    Ok(message_sets) => message_sets,
+                    Err(e) => {
+                        warn!("Kafka poll failed; Retrying in {:?} ...; Cause: {}", POLL_RETRY_DELAY, e);
+                        thread::sleep(POLL_RETRY_DELAY);
+                        continue;
+                    }
+                };
+                if message_sets.is_empty() {
+                    continue;
+                }
+                for ms in message_sets.iter() {
+                    for m in ms.messages() {
+                        let messages: Vec<[u8; 4]> = match (self.value_parser)(m.value) {
+                            Ok(msg_vec) => msg_vec,
+                            Err(e) => {
+                                warn!("Parsing of message `{:?}` failed; Cause: {}. Skipping ...", m.value, e);
+                                continue;
+                            }
+                        };
+                        match self.sender.send(messages) {
+                            Ok(()) => {},
+                            Err(e) => {
+                                return Err(format!("Can not send Aggregator event via event queue; Cause: {}", e))
+                            }
                         }
                     }
+                    if let Err(e) = self.consumer.consume_messageset(ms) {
+                        return Err(format!("Kafka couldn't mark message set as consumed; Cause: {}", e));
+                    }
+                }
+                if self.commit_offsets {
+                    if let Err(e) = self.consumer.commit_consumed() {
+                        warn!("Kafka offset commit failed, will retry with next poll; Cause: {}", e);
+                    }
                 }
             }
             Ok(())
         }
+
+        /// Polls return within the fetch wait time, so the flag is seen without waking up.
+        fn stop_hook(&self) -> StopHook {
+            let stopped = self.stopped.clone();
+            Box::new(move || stopped.stop())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_fetch_offset_by_name() {
+            match fetch_offset_by_name("earliest") {
+                Ok(FetchOffset::Earliest) => {}
+                _ => panic!("Expected earliest fetch offset")
+            }
+            assert!(fetch_offset_by_name("newest").is_err());
+        }
     }
 }
 
 pub mod udp {
-    use std::net::UdpSocket;
-    use super::{IpSender, Listener, StreamParser, AggEvent};
+    use std::net::{SocketAddr, UdpSocket};
+    use super::{IpSender, Listener, StopFlag, StopHook, StreamParser, wake_address};
 
     pub struct UdpServer {
         socket: UdpSocket,
         sender: IpSender,
         parser: StreamParser,
+        stopped: StopFlag,
     }
 
 
@@ -118,15 +395,22 @@ pub mod udp {
         pub fn new(address: &str, parser: StreamParser, sender: IpSender) -> Result<UdpServer, String> {
             match UdpSocket::bind(address) {
                 Ok(socket) => {
-                    Ok(UdpServer { socket, sender, parser })
+                    Ok(UdpServer { socket, sender, parser, stopped: StopFlag::default() })
                 }
                 Err(err) => Err(format!("Can not start UdpServer; Cause: {}", err))
             }
         }
+    }
 
-        pub fn shutdown(&self) -> () {
-            self.socket.send("STOP!".as_bytes()).unwrap();
-        }
+    /// Sends an empty datagram to the server bound to `address`, which wakes it up from waiting
+    /// for a datagram, so it notices it was stopped.
+    fn send_wake_up(address: SocketAddr) -> Result<(), String> {
+        let target = wake_address(address);
+        let bind_address = if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        UdpSocket::bind(bind_address)
+            .and_then(|socket| socket.send_to(&[], target))
+            .map(|_| ())
+            .map_err(|e| format!("Can not stop UdpServer on `{}`; Cause: {}", address, e))
     }
 
     impl Listener for UdpServer {
@@ -136,7 +420,7 @@ pub mod udp {
             loop {
                 match self.socket.recv_from(&mut buffer) {
                     Ok((size, _)) => {
-                        if &buffer[0..size] == "STOP!".as_bytes() {
+                        if self.stopped.is_stopped() {
                             return Ok(())
                         }
                         let data = match (self.parser)(&buffer[0..size]) {
@@ -146,7 +430,7 @@ pub mod udp {
                                 continue;
                             }
                         };
-                        match self.sender.send(AggEvent::ADD(data)) {
+                        match self.sender.send(data) {
                             Err(e) => return Err(format!("Can not send Aggregator event via event queue; Cause: {}", e)),
                             _ => {}
                         }
@@ -157,48 +441,1100 @@ pub mod udp {
                 }
             }
         }
+
+        fn stop_hook(&self) -> StopHook {
+            let stopped = self.stopped.clone();
+            let address = self.socket.local_addr().ok();
+            Box::new(move || {
+                stopped.stop();
+                if let Some(Err(e)) = address.map(send_wake_up) {
+                    warn!("{}", e);
+                }
+            })
+        }
     }
 
     #[cfg(test)]
     mod tests {
+        use ipagg::AggEvent;
         use parsers::simple_parser;
         use super::*;
 
         #[test]
         fn test_udp_server_listener() {
             use std::thread;
-            use std::sync::mpsc::channel;
+            use std::sync::mpsc::sync_channel;
+
+            let (tx, rx) = sync_channel(10);
+            let mut serv = UdpServer::new("127.0.0.1:12345", simple_parser, IpSender::new(tx, None)).unwrap();
+            let stop = serv.stop_hook();
+            let handle = thread::spawn(move || serv.listen());
+
+            let socket = UdpSocket::bind("127.0.0.1:12341").unwrap();
+            socket.send_to(b"192.168.1.1 127.0.0.1 172.16.100.10", "127.0.0.1:12345").unwrap();
+            socket.send_to(b"STOP!", "127.0.0.1:12345").unwrap();
+
+            let data: Vec<[u8; 4]> = match rx.recv().unwrap() {
+                AggEvent::ADD(data, _) => data,
+                _ => panic!("This shouldn't happened!")
+            };
+            assert_eq!(vec![[192, 168, 1, 1], [127, 0, 0, 1], [172, 16, 100, 10]], data);
+            stop();
+            assert_eq!(Ok(()), handle.join().unwrap());
+        }
+
+        #[test]
+        fn test_udp_server_stop_hook() {
+            use std::thread;
+            use std::sync::mpsc::sync_channel;
 
-            let (tx, rx) = channel();
-            let (lock_tx, lock_rx) = channel();
+            let (tx, _rx) = sync_channel(10);
+            let mut serv = UdpServer::new("0.0.0.0:12346", simple_parser, IpSender::new(tx, None)).unwrap();
+            let stop = serv.stop_hook();
+            let handle = thread::spawn(move || serv.listen());
+            stop();
+            assert_eq!(Ok(()), handle.join().unwrap());
+        }
+    }
+}
 
-            let mut handles = Vec::new();
+pub mod tcp {
+    use std::net::TcpListener;
+    use std::thread;
+    use framing::Framing;
+    use std::time::Duration;
+    use super::{ConnectionLimit, IpSender, Listener, StopFlag, StopHook, StreamParser, MAX_CONNECTIONS, READ_TIMEOUT_SECS,
+                handle_framed_stream, tcp_stop_hook};
+
+    /// Accepts up to `MAX_CONNECTIONS` concurrent connections, each handled on its own thread;
+    /// further ones are closed right away, as are connections idle for `READ_TIMEOUT_SECS`. Frames
+    /// are pushed to the shared event queue with a blocking send, so once the queue is full the
+    /// connection stops being read and TCP flow control pushes back on the producer.
+    pub struct TcpServer {
+        listener: TcpListener,
+        framing: Framing,
+        sender: IpSender,
+        parser: StreamParser,
+        connections: ConnectionLimit,
+        stopped: StopFlag,
+    }
+
+
+    impl TcpServer {
+        pub fn new(address: &str, framing: Framing, parser: StreamParser, sender: IpSender) -> Result<TcpServer, String> {
+            match TcpListener::bind(address) {
+                Ok(listener) => {
+                    Ok(TcpServer {
+                        listener, framing, sender, parser,
+                        connections: ConnectionLimit::new(MAX_CONNECTIONS),
+                        stopped: StopFlag::default(),
+                    })
+                }
+                Err(err) => Err(format!("Can not start TcpServer; Cause: {}", err))
+            }
+        }
+    }
 
-            handles.push(thread::spawn(move || {
-                let mut serv = UdpServer::new("127.0.0.1:12345", simple_parser, tx).unwrap();
-                lock_tx.send("".to_owned()).unwrap();
+    impl Listener for TcpServer {
+        fn listen(&mut self) -> Result<(), String> {
+            for stream in self.listener.incoming() {
+                if self.stopped.is_stopped() {
+                    break;
+                }
+                match stream {
+                    Ok(stream) => {
+                        let peer = match stream.peer_addr() {
+                            Ok(addr) => addr.to_string(),
+                            Err(_) => "unknown peer".to_owned()
+                        };
+                        let slot = match self.connections.acquire() {
+                            Some(slot) => slot,
+                            None => {
+                                warn!("TcpServer refused connection from {}; Cause: {} connections are open", peer, self.connections.max);
+                                continue;
+                            }
+                        };
+                        if let Err(e) = stream.set_read_timeout(Some(Duration::from_secs(READ_TIMEOUT_SECS))) {
+                            warn!("TcpServer could not handle connection from {}; Cause: {}", peer, e);
+                            continue;
+                        }
+                        let (framing, parser, sender) = (self.framing, self.parser, self.sender.clone());
+                        thread::spawn(move || {
+                            let _slot = slot;
+                            handle_framed_stream(stream, &peer, framing, parser, sender);
+                        });
+                    }
+                    Err(err) => {
+                        warn!("TcpServer could not accept connection; Cause: {}", err);
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        fn stop_hook(&self) -> StopHook {
+            tcp_stop_hook(&self.stopped, self.listener.local_addr().ok())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use ipagg::AggEvent;
+        use parsers::simple_parser;
+        use std::net::TcpStream;
+        use super::*;
+
+        #[test]
+        fn test_tcp_server_listener() {
+            use std::io::Write;
+            use std::sync::mpsc::sync_channel;
+
+            let (tx, rx) = sync_channel(10);
+            let mut serv = TcpServer::new("127.0.0.1:12355", Framing::Newline, simple_parser, IpSender::new(tx, None)).unwrap();
+            thread::spawn(move || {
                 serv.listen().unwrap();
-            }));
+            });
 
-            lock_rx.recv().unwrap();
+            let mut first = TcpStream::connect("127.0.0.1:12355").unwrap();
+            let mut second = TcpStream::connect("127.0.0.1:12355").unwrap();
+            first.write_all(b"192.168.1.1 127.0.0.1\n").unwrap();
+            second.write_all(b"172.16.100.10\n").unwrap();
 
-            handles.push(thread::spawn(move || {
-                let mut socket = UdpSocket::bind("127.0.0.1:12341").unwrap();
-                let addresses = b"192.168.1.1 127.0.0.1 172.16.100.10";
-                socket.send_to(addresses, "127.0.0.1:12345").unwrap();
-                socket.send_to(b"STOP!", "127.0.0.1:12345");
-                drop(socket);
-            }));
+            let mut data: Vec<Vec<[u8; 4]>> = (0..2).map(|_| {
+                match rx.recv().unwrap() {
+                    AggEvent::ADD(data, _) => data,
+                    _ => panic!("This shouldn't happened!")
+                }
+            }).collect();
+            data.sort();
+            assert_eq!(vec![vec![[172, 16, 100, 10]], vec![[192, 168, 1, 1], [127, 0, 0, 1]]], data);
+        }
 
-            let data: Vec<[u8; 4]> = match rx.recv().unwrap() {
-                AggEvent::ADD(data) => data,
+        #[test]
+        fn test_tcp_server_connection_limit() {
+            use std::io::Read;
+            use std::sync::mpsc::sync_channel;
+            use listeners::ConnectionLimit;
+
+            let (tx, _rx) = sync_channel(10);
+            let mut serv = TcpServer::new("127.0.0.1:12357", Framing::Newline, simple_parser, IpSender::new(tx, None)).unwrap();
+            serv.connections = ConnectionLimit::new(1);
+            thread::spawn(move || {
+                serv.listen().unwrap();
+            });
+
+            let idle = TcpStream::connect("127.0.0.1:12357").unwrap();
+            let mut refused = TcpStream::connect("127.0.0.1:12357").unwrap();
+            assert_eq!(0, refused.read(&mut [0; 16]).unwrap());
+            drop(idle);
+        }
+
+        #[test]
+        fn test_tcp_server_listener_length_prefixed() {
+            use std::io::Write;
+            use std::sync::mpsc::sync_channel;
+
+            let (tx, rx) = sync_channel(10);
+            let mut serv = TcpServer::new("127.0.0.1:12356", Framing::LengthPrefixed, simple_parser,
+                                          IpSender::new(tx, Some("tcp-in".to_owned()))).unwrap();
+            thread::spawn(move || {
+                serv.listen().unwrap();
+            });
+
+            let mut stream = TcpStream::connect("127.0.0.1:12356").unwrap();
+            stream.write_all(b"\x00\x00\x00\x0b192.168.1.1").unwrap();
+
+            match rx.recv().unwrap() {
+                AggEvent::ADD(data, source) => {
+                    assert_eq!(vec![[192, 168, 1, 1]], data);
+                    assert_eq!(Some("tcp-in".to_owned()), source);
+                }
                 _ => panic!("This shouldn't happened!")
+            }
+        }
+    }
+}
+
+pub mod unix {
+    use std::fs;
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    use std::os::unix::net::{UnixDatagram, UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+    use std::thread;
+    use std::time::Duration;
+    use framing::Framing;
+    use super::{ConnectionLimit, IpSender, Listener, StopFlag, StopHook, StreamParser, MAX_CONNECTIONS, READ_TIMEOUT_SECS,
+                handle_framed_stream};
+
+    /// Removes a socket file left behind by a previous run. Regular files are never touched and
+    /// a socket that still accepts connections is reported as being in use.
+    fn remove_stale_socket(path: &Path, datagram: bool) -> Result<(), String> {
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(_) => return Ok(())
+        };
+        if !metadata.file_type().is_socket() {
+            return Err(format!("`{}` exists and is not a socket", path.display()));
+        }
+        let in_use = if datagram {
+            UnixDatagram::unbound().and_then(|socket| socket.connect(path)).is_ok()
+        } else {
+            UnixStream::connect(path).is_ok()
+        };
+        if in_use {
+            return Err(format!("Socket `{}` is in use by another process", path.display()));
+        }
+        info!("Removing stale socket `{}`", path.display());
+        fs::remove_file(path).map_err(|e| format!("Can not remove stale socket `{}`; Cause: {}", path.display(), e))
+    }
+
+    fn apply_permissions(path: &Path, permissions: Option<u32>) -> Result<(), String> {
+        match permissions {
+            Some(mode) => fs::set_permissions(path, fs::Permissions::from_mode(mode))
+                .map_err(|e| format!("Can not set permissions of `{}`; Cause: {}", path.display(), e)),
+            None => Ok(())
+        }
+    }
+
+    /// Serves connections like `TcpServer`, with the same limits. Socket file is removed once
+    /// the server is dropped.
+    pub struct UnixStreamServer {
+        listener: UnixListener,
+        path: PathBuf,
+        framing: Framing,
+        sender: IpSender,
+        parser: StreamParser,
+        connections: ConnectionLimit,
+        stopped: StopFlag,
+    }
+
+    impl UnixStreamServer {
+        pub fn new(path: &str, permissions: Option<u32>, framing: Framing, parser: StreamParser, sender: IpSender)
+                   -> Result<UnixStreamServer, String> {
+            let path = PathBuf::from(path);
+            remove_stale_socket(&path, false)?;
+            match UnixListener::bind(&path) {
+                Ok(listener) => {
+                    apply_permissions(&path, permissions)?;
+                    Ok(UnixStreamServer {
+                        listener, path, framing, sender, parser,
+                        connections: ConnectionLimit::new(MAX_CONNECTIONS),
+                        stopped: StopFlag::default(),
+                    })
+                }
+                Err(err) => Err(format!("Can not start UnixStreamServer; Cause: {}", err))
+            }
+        }
+    }
+
+    impl Listener for UnixStreamServer {
+        fn listen(&mut self) -> Result<(), String> {
+            for stream in self.listener.incoming() {
+                if self.stopped.is_stopped() {
+                    break;
+                }
+                match stream {
+                    Ok(stream) => {
+                        let peer = self.path.display().to_string();
+                        let slot = match self.connections.acquire() {
+                            Some(slot) => slot,
+                            None => {
+                                warn!("UnixStreamServer refused connection on {}; Cause: {} connections are open", peer, self.connections.max);
+                                continue;
+                            }
+                        };
+                        if let Err(e) = stream.set_read_timeout(Some(Duration::from_secs(READ_TIMEOUT_SECS))) {
+                            warn!("UnixStreamServer could not handle connection on {}; Cause: {}", peer, e);
+                            continue;
+                        }
+                        let (framing, parser, sender) = (self.framing, self.parser, self.sender.clone());
+                        thread::spawn(move || {
+                            let _slot = slot;
+                            handle_framed_stream(stream, &peer, framing, parser, sender);
+                        });
+                    }
+                    Err(err) => {
+                        warn!("UnixStreamServer could not accept connection; Cause: {}", err);
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        fn stop_hook(&self) -> StopHook {
+            let (stopped, path) = (self.stopped.clone(), self.path.clone());
+            Box::new(move || {
+                stopped.stop();
+                if let Err(e) = UnixStream::connect(&path) {
+                    warn!("Can not wake up UnixStreamServer on `{}`; Cause: {}", path.display(), e);
+                }
+            })
+        }
+    }
+
+    impl Drop for UnixStreamServer {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    /// Socket file is removed once the server is dropped.
+    pub struct UnixDatagramServer {
+        socket: UnixDatagram,
+        path: PathBuf,
+        sender: IpSender,
+        parser: StreamParser,
+        stopped: StopFlag,
+    }
+
+    impl UnixDatagramServer {
+        pub fn new(path: &str, permissions: Option<u32>, parser: StreamParser, sender: IpSender)
+                   -> Result<UnixDatagramServer, String> {
+            let path = PathBuf::from(path);
+            remove_stale_socket(&path, true)?;
+            match UnixDatagram::bind(&path) {
+                Ok(socket) => {
+                    apply_permissions(&path, permissions)?;
+                    Ok(UnixDatagramServer { socket, path, sender, parser, stopped: StopFlag::default() })
+                }
+                Err(err) => Err(format!("Can not start UnixDatagramServer; Cause: {}", err))
+            }
+        }
+    }
+
+    impl Listener for UnixDatagramServer {
+        fn listen(&mut self) -> Result<(), String> {
+            let mut buffer = vec![0_u8; 65536];
+
+            loop {
+                match self.socket.recv(&mut buffer) {
+                    Ok(_) if self.stopped.is_stopped() => return Ok(()),
+                    Ok(size) => {
+                        let data = match (self.parser)(&buffer[0..size]) {
+                            Ok(ips) => ips,
+                            Err(e) => {
+                                warn!("Parsing of message {:?} failed. Skipping ...; Cause: {}", &buffer[0..size], e);
+                                continue;
+                            }
+                        };
+                        if let Err(e) = self.sender.send(data) {
+                            return Err(format!("Can not send Aggregator event via event queue; Cause: {}", e));
+                        }
+                    }
+                    Err(err) => {
+                        return Err(format!("UnixDatagramServer stopped working due to: {}", err));
+                    }
+                }
+            }
+        }
+
+        fn stop_hook(&self) -> StopHook {
+            let (stopped, path) = (self.stopped.clone(), self.path.clone());
+            Box::new(move || {
+                stopped.stop();
+                if let Err(e) = UnixDatagram::unbound().and_then(|socket| socket.send_to(b"", &path)) {
+                    warn!("Can not wake up UnixDatagramServer on `{}`; Cause: {}", path.display(), e);
+                }
+            })
+        }
+    }
+
+    impl Drop for UnixDatagramServer {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use ipagg::AggEvent;
+        use parsers::simple_parser;
+        use std::env::temp_dir;
+        use std::sync::mpsc::sync_channel;
+        use super::*;
+
+        fn socket_path(name: &str) -> String {
+            temp_dir().join(name).display().to_string()
+        }
+
+        #[test]
+        fn test_unix_stream_server_listener() {
+            use std::io::Write;
+
+            let path = socket_path("ipaggregator-test-stream.sock");
+            let (tx, rx) = sync_channel(10);
+            let mut serv = UnixStreamServer::new(&path, Some(0o600), Framing::Newline, simple_parser, IpSender::new(tx, None)).unwrap();
+            assert_eq!(0o600, fs::metadata(&path).unwrap().permissions().mode() & 0o777);
+            thread::spawn(move || {
+                serv.listen().unwrap();
+            });
+
+            let mut stream = UnixStream::connect(&path).unwrap();
+            stream.write_all(b"192.168.1.1 127.0.0.1\n").unwrap();
+
+            match rx.recv().unwrap() {
+                AggEvent::ADD(data, _) => assert_eq!(vec![[192, 168, 1, 1], [127, 0, 0, 1]], data),
+                _ => panic!("This shouldn't happened!")
+            }
+        }
+
+        #[test]
+        fn test_unix_servers_stop_and_remove_socket() {
+            let stream_path = socket_path("ipaggregator-test-stream-stop.sock");
+            let dgram_path = socket_path("ipaggregator-test-dgram-stop.sock");
+            let (tx, _rx) = sync_channel(10);
+            let servers: Vec<Box<Listener + Send>> = vec![
+                Box::new(UnixStreamServer::new(&stream_path, None, Framing::Newline, simple_parser, IpSender::new(tx.clone(), None)).unwrap()),
+                Box::new(UnixDatagramServer::new(&dgram_path, None, simple_parser, IpSender::new(tx, None)).unwrap())];
+            for mut serv in servers {
+                let stop = serv.stop_hook();
+                let handle = thread::spawn(move || serv.listen());
+                stop();
+                assert_eq!(Ok(()), handle.join().unwrap());
+            }
+            assert!(fs::symlink_metadata(&stream_path).is_err());
+            assert!(fs::symlink_metadata(&dgram_path).is_err());
+        }
+
+        #[test]
+        fn test_unix_datagram_server_replaces_stale_socket() {
+            let path = socket_path("ipaggregator-test-dgram.sock");
+            let _ = fs::remove_file(&path);
+            drop(UnixDatagram::bind(&path).unwrap());
+            assert!(fs::symlink_metadata(&path).is_ok());
+
+            let (tx, rx) = sync_channel(10);
+            let mut serv = UnixDatagramServer::new(&path, None, simple_parser, IpSender::new(tx, None)).unwrap();
+            thread::spawn(move || {
+                serv.listen().unwrap();
+            });
+
+            let client = UnixDatagram::unbound().unwrap();
+            client.send_to(b"172.16.100.10", &path).unwrap();
+
+            match rx.recv().unwrap() {
+                AggEvent::ADD(data, _) => assert_eq!(vec![[172, 16, 100, 10]], data),
+                _ => panic!("This shouldn't happened!")
+            }
+        }
+
+        #[test]
+        fn test_unix_datagram_server_refuses_socket_in_use() {
+            let path = socket_path("ipaggregator-test-dgram-in-use.sock");
+            let _ = fs::remove_file(&path);
+            let bound = UnixDatagram::bind(&path).unwrap();
+
+            let (tx, _rx) = sync_channel(10);
+            assert!(UnixDatagramServer::new(&path, None, simple_parser, IpSender::new(tx, None)).is_err());
+            assert!(fs::symlink_metadata(&path).is_ok());
+            drop(bound);
+            fs::remove_file(&path).unwrap();
+        }
+
+        #[test]
+        fn test_remove_stale_socket_keeps_regular_files() {
+            let path = socket_path("ipaggregator-test-regular-file.sock");
+            fs::write(&path, b"").unwrap();
+            assert!(remove_stale_socket(Path::new(&path), false).is_err());
+            assert!(fs::metadata(&path).is_ok());
+            fs::remove_file(&path).unwrap();
+        }
+    }
+}
+
+pub mod tail {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::fs::File;
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use std::os::unix::fs::MetadataExt;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::Duration;
+    use super::{IpSender, Listener, StopFlag, StopHook, StreamParser};
+
+    const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+    struct TailedFile {
+        path: PathBuf,
+        file: Option<File>,
+        inode: u64,
+        // Bytes of the current file already handed over as complete lines.
+        offset: u64,
+        // Trailing bytes of an unfinished line.
+        pending: Vec<u8>,
+    }
+
+    /// Follows files the way `tail -F` does: a renamed file is read to its end before the new
+    /// file at the same path is opened, and a truncated file is read again from the beginning.
+    /// Read offsets are kept in an optional state file, keyed by path and inode.
+    pub struct FileTailer {
+        files: Vec<TailedFile>,
+        state_file: Option<PathBuf>,
+        saved_offsets: HashMap<PathBuf, (u64, u64)>,
+        poll_interval: Duration,
+        sender: IpSender,
+        parser: StreamParser,
+        stopped: StopFlag,
+    }
+
+    impl FileTailer {
+        pub fn new(paths: Vec<String>, state_file: Option<String>, poll_interval: Duration,
+                   parser: StreamParser, sender: IpSender) -> Result<FileTailer, String> {
+            if paths.is_empty() {
+                return Err("FileTailer needs at least one file to follow".to_owned());
+            }
+            let state_file = state_file.map(PathBuf::from);
+            let saved_offsets = match state_file {
+                Some(ref path) => load_state(path)?,
+                None => HashMap::new()
             };
+            let files = paths.into_iter().map(|path| TailedFile {
+                path: PathBuf::from(path),
+                file: None,
+                inode: 0,
+                offset: 0,
+                pending: Vec::new(),
+            }).collect();
+            Ok(FileTailer { files, state_file, saved_offsets, poll_interval, sender, parser, stopped: StopFlag::default() })
+        }
 
-            for handle in handles {
-                handle.join().unwrap();
+        /// Reads everything appended since the last poll. Returns whether any file advanced.
+        pub fn poll(&mut self) -> Result<bool, String> {
+            let mut advanced = false;
+            for idx in 0..self.files.len() {
+                advanced |= self.poll_file(idx)?;
+            }
+            if advanced {
+                self.save_state()?;
+            }
+            Ok(advanced)
+        }
+
+        fn poll_file(&mut self, idx: usize) -> Result<bool, String> {
+            let mut advanced = false;
+            if self.files[idx].file.is_none() && !self.open_file(idx) {
+                return Ok(false);
+            }
+            if self.is_truncated(idx) {
+                info!("File `{}` was truncated; Reading from beginning", self.files[idx].path.display());
+                let tailed = &mut self.files[idx];
+                tailed.offset = 0;
+                tailed.pending.clear();
+                if let Some(ref mut file) = tailed.file {
+                    file.seek(SeekFrom::Start(0)).map_err(|e| format!("Can not seek `{}`; Cause: {}", tailed.path.display(), e))?;
+                }
+            }
+            advanced |= self.read_to_end(idx)?;
+            if self.is_rotated(idx) {
+                info!("File `{}` was rotated; Following new file", self.files[idx].path.display());
+                let pending = {
+                    let tailed = &mut self.files[idx];
+                    tailed.file = None;
+                    tailed.offset = 0;
+                    tailed.pending.split_off(0)
+                };
+                self.send_line(&pending)?;
+                if self.open_file(idx) {
+                    self.read_to_end(idx)?;
+                }
+                advanced = true;
+            }
+            Ok(advanced)
+        }
+
+        fn open_file(&mut self, idx: usize) -> bool {
+            let tailed = &mut self.files[idx];
+            let mut file = match File::open(&tailed.path) {
+                Ok(file) => file,
+                Err(_) => return false
+            };
+            tailed.inode = match file.metadata() {
+                Ok(metadata) => metadata.ino(),
+                Err(_) => return false
+            };
+            tailed.offset = 0;
+            tailed.pending.clear();
+            if let Some(&(inode, offset)) = self.saved_offsets.get(&tailed.path) {
+                if inode == tailed.inode && file.seek(SeekFrom::Start(offset)).is_ok() {
+                    tailed.offset = offset;
+                }
+            }
+            self.saved_offsets.remove(&tailed.path);
+            tailed.file = Some(file);
+            true
+        }
+
+        fn is_truncated(&self, idx: usize) -> bool {
+            let tailed = &self.files[idx];
+            match tailed.file {
+                Some(ref file) => match file.metadata() {
+                    Ok(metadata) => metadata.len() < tailed.offset + tailed.pending.len() as u64,
+                    Err(_) => false
+                },
+                None => false
+            }
+        }
+
+        fn is_rotated(&self, idx: usize) -> bool {
+            let tailed = &self.files[idx];
+            match fs::metadata(&tailed.path) {
+                Ok(metadata) => metadata.ino() != tailed.inode,
+                Err(_) => false
             }
-            assert_eq!(vec![[192, 168, 1, 1], [127, 0, 0, 1], [172, 16, 100, 10]], data);
         }
+
+        fn read_to_end(&mut self, idx: usize) -> Result<bool, String> {
+            let mut buffer = vec![0_u8; READ_CHUNK_SIZE];
+            let mut advanced = false;
+            loop {
+                let size = {
+                    let tailed = &mut self.files[idx];
+                    let file = match tailed.file {
+                        Some(ref mut file) => file,
+                        None => return Ok(advanced)
+                    };
+                    match file.read(&mut buffer) {
+                        Ok(0) => return Ok(advanced),
+                        Ok(size) => size,
+                        Err(e) => return Err(format!("Can not read `{}`; Cause: {}", tailed.path.display(), e))
+                    }
+                };
+                advanced = true;
+                let mut lines = {
+                    let tailed = &mut self.files[idx];
+                    tailed.pending.extend_from_slice(&buffer[..size]);
+                    match tailed.pending.iter().rposition(|&byte| byte == b'\n') {
+                        Some(last) => {
+                            let rest = tailed.pending.split_off(last + 1);
+                            tailed.offset += tailed.pending.len() as u64;
+                            ::std::mem::replace(&mut tailed.pending, rest)
+                        }
+                        None => continue
+                    }
+                };
+                lines.pop();
+                for line in lines.split(|&byte| byte == b'\n') {
+                    self.send_line(line)?;
+                }
+            }
+        }
+
+        fn send_line(&self, line: &[u8]) -> Result<(), String> {
+            if line.iter().all(|byte| byte.is_ascii_whitespace()) {
+                return Ok(());
+            }
+            let data = match (self.parser)(line) {
+                Ok(ips) => ips,
+                Err(e) => {
+                    warn!("Parsing of line {:?} failed. Skipping ...; Cause: {}", line, e);
+                    return Ok(());
+                }
+            };
+            self.sender.send(data)
+                .map_err(|e| format!("Can not send Aggregator event via event queue; Cause: {}", e))
+        }
+
+        fn save_state(&self) -> Result<(), String> {
+            let path = match self.state_file {
+                Some(ref path) => path,
+                None => return Ok(())
+            };
+            let mut content = String::new();
+            for tailed in self.files.iter().filter(|tailed| tailed.file.is_some()) {
+                content.push_str(&format!("{} {} {}\n", tailed.inode, tailed.offset, tailed.path.display()));
+            }
+            let tmp_path = path.with_extension("tmp");
+            File::create(&tmp_path)
+                .and_then(|mut file| file.write_all(content.as_bytes()))
+                .and_then(|_| fs::rename(&tmp_path, path))
+                .map_err(|e| format!("Can not write tail state file `{}`; Cause: {}", path.display(), e))
+        }
+    }
+
+    fn load_state(path: &PathBuf) -> Result<HashMap<PathBuf, (u64, u64)>, String> {
+        let mut offsets = HashMap::new();
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(_) => return Ok(offsets)
+        };
+        for line in content.lines() {
+            let mut fields = line.splitn(3, ' ');
+            match (fields.next().and_then(|f| f.parse().ok()), fields.next().and_then(|f| f.parse().ok()), fields.next()) {
+                (Some(inode), Some(offset), Some(file_path)) => {
+                    offsets.insert(PathBuf::from(file_path), (inode, offset));
+                }
+                _ => warn!("Invalid line `{}` in tail state file `{}`. Skipping ...", line, path.display())
+            }
+        }
+        Ok(offsets)
     }
-}
\ No newline at end of file
+
+    impl Listener for FileTailer {
+        /// Offsets are saved once more when stopped.
+        fn listen(&mut self) -> Result<(), String> {
+            while !self.stopped.is_stopped() {
+                if !self.poll()? {
+                    thread::sleep(self.poll_interval);
+                }
+            }
+            self.save_state()
+        }
+
+        fn stop_hook(&self) -> StopHook {
+            let stopped = self.stopped.clone();
+            Box::new(move || stopped.stop())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use ipagg::AggEvent;
+        use parsers::simple_parser;
+        use std::env::temp_dir;
+        use std::fs::OpenOptions;
+        use std::sync::mpsc::{sync_channel, Receiver};
+        use super::*;
+
+        fn append(path: &PathBuf, data: &[u8]) {
+            OpenOptions::new().create(true).append(true).open(path).unwrap().write_all(data).unwrap();
+        }
+
+        fn received(rx: &Receiver<AggEvent>) -> Vec<[u8; 4]> {
+            let mut ips = Vec::new();
+            while let Ok(AggEvent::ADD(data, _)) = rx.try_recv() {
+                ips.extend(data);
+            }
+            ips
+        }
+
+        fn test_paths(name: &str) -> (PathBuf, PathBuf) {
+            let log = temp_dir().join(format!("ipaggregator-test-{}.log", name));
+            let state = temp_dir().join(format!("ipaggregator-test-{}.state", name));
+            let _ = fs::remove_file(&log);
+            let _ = fs::remove_file(&state);
+            (log, state)
+        }
+
+        #[test]
+        fn test_tailer_reads_complete_lines() {
+            let (log, _) = test_paths("lines");
+            let (tx, rx) = sync_channel(100);
+            let mut tailer = FileTailer::new(vec![log.display().to_string()], None, Duration::from_millis(10),
+                                             simple_parser, IpSender::new(tx, None)).unwrap();
+            assert!(!tailer.poll().unwrap());
+
+            append(&log, b"10.0.0.1\n10.0.0.2");
+            tailer.poll().unwrap();
+            assert_eq!(vec![[10, 0, 0, 1]], received(&rx));
+
+            append(&log, b"\n");
+            tailer.poll().unwrap();
+            assert_eq!(vec![[10, 0, 0, 2]], received(&rx));
+        }
+
+        #[test]
+        fn test_tailer_follows_rotation_and_truncation() {
+            let (log, _) = test_paths("rotate");
+            let rotated = log.with_extension("log.1");
+            let (tx, rx) = sync_channel(100);
+            let mut tailer = FileTailer::new(vec![log.display().to_string()], None, Duration::from_millis(10),
+                                             simple_parser, IpSender::new(tx, None)).unwrap();
+            append(&log, b"10.0.0.1\n");
+            tailer.poll().unwrap();
+            assert_eq!(vec![[10, 0, 0, 1]], received(&rx));
+
+            append(&log, b"10.0.0.2\n");
+            fs::rename(&log, &rotated).unwrap();
+            append(&log, b"10.0.0.3\n");
+            tailer.poll().unwrap();
+            assert_eq!(vec![[10, 0, 0, 2], [10, 0, 0, 3]], received(&rx));
+
+            fs::write(&log, b"").unwrap();
+            tailer.poll().unwrap();
+            append(&log, b"10.0.0.4\n");
+            tailer.poll().unwrap();
+            assert_eq!(vec![[10, 0, 0, 4]], received(&rx));
+            fs::remove_file(&rotated).unwrap();
+        }
+
+        #[test]
+        fn test_tailer_resumes_from_state_file() {
+            let (log, state) = test_paths("state");
+            let (tx, rx) = sync_channel(100);
+            append(&log, b"10.0.0.1\n");
+            {
+                let mut tailer = FileTailer::new(vec![log.display().to_string()], Some(state.display().to_string()),
+                                                 Duration::from_millis(10), simple_parser, IpSender::new(tx.clone(), None)).unwrap();
+                tailer.poll().unwrap();
+            }
+            assert_eq!(vec![[10, 0, 0, 1]], received(&rx));
+
+            append(&log, b"10.0.0.2\n");
+            let mut tailer = FileTailer::new(vec![log.display().to_string()], Some(state.display().to_string()),
+                                             Duration::from_millis(10), simple_parser, IpSender::new(tx, None)).unwrap();
+            tailer.poll().unwrap();
+            assert_eq!(vec![[10, 0, 0, 2]], received(&rx));
+        }
+
+        #[test]
+        fn test_tailer_saves_state_when_stopped() {
+            let (log, state) = test_paths("stop");
+            let (tx, rx) = sync_channel(100);
+            append(&log, b"10.0.0.1\n");
+            let mut tailer = FileTailer::new(vec![log.display().to_string()], Some(state.display().to_string()),
+                                             Duration::from_millis(10), simple_parser, IpSender::new(tx, None)).unwrap();
+            let stop = tailer.stop_hook();
+            let handle = thread::spawn(move || tailer.listen());
+            while rx.try_recv().is_err() {
+                thread::sleep(Duration::from_millis(10));
+            }
+            stop();
+            assert_eq!(Ok(()), handle.join().unwrap());
+            assert!(fs::read_to_string(&state).unwrap().ends_with(&format!(" 9 {}\n", log.display())));
+        }
+    }
+}
+
+pub mod http {
+    use serde_json;
+    use std::io::BufReader;
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+    use std::str::FromStr;
+    use std::sync::mpsc::TrySendError;
+    use std::thread;
+    use std::time::Duration;
+    use http::{read_request, write_response, HttpError, Request};
+    use super::{ConnectionLimit, IpSender, Listener, StopFlag, StopHook, StreamParser, MAX_CONNECTIONS, READ_TIMEOUT_SECS,
+                tcp_stop_hook};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum JsonBody {
+        List(Vec<String>),
+        Object { ips: Vec<String> },
+    }
+
+    /// Accepts `POST /ingest` with a `text/plain` body (handed to the configured parser), a JSON
+    /// list of addresses (or `{"ips": [...]}`) or `application/octet-stream` with 4 bytes per
+    /// address. Answers `202` when queued and `429` when the aggregator queue is full. At most
+    /// `MAX_CONNECTIONS` connections are served at once, further ones get `503`.
+    pub struct HttpServer {
+        listener: TcpListener,
+        sender: IpSender,
+        parser: StreamParser,
+        connections: ConnectionLimit,
+        stopped: StopFlag,
+    }
+
+    impl HttpServer {
+        pub fn new(address: &str, parser: StreamParser, sender: IpSender) -> Result<HttpServer, String> {
+            match TcpListener::bind(address) {
+                Ok(listener) => Ok(HttpServer {
+                    listener,
+                    sender,
+                    parser,
+                    connections: ConnectionLimit::new(MAX_CONNECTIONS),
+                    stopped: StopFlag::default(),
+                }),
+                Err(err) => Err(format!("Can not start HttpServer; Cause: {}", err))
+            }
+        }
+    }
+
+    impl Listener for HttpServer {
+        fn listen(&mut self) -> Result<(), String> {
+            for stream in self.listener.incoming() {
+                if self.stopped.is_stopped() {
+                    break;
+                }
+                match stream {
+                    Ok(mut stream) => {
+                        let slot = match self.connections.acquire() {
+                            Some(slot) => slot,
+                            None => {
+                                warn!("HttpServer refused connection; Cause: {} connections are open", self.connections.max);
+                                let _ = write_response(&mut stream, 503, "Too many connections, retry later");
+                                continue;
+                            }
+                        };
+                        if let Err(e) = stream.set_read_timeout(Some(Duration::from_secs(READ_TIMEOUT_SECS))) {
+                            warn!("HttpServer could not handle connection; Cause: {}", e);
+                            continue;
+                        }
+                        let (parser, sender) = (self.parser, self.sender.clone());
+                        thread::spawn(move || {
+                            let _slot = slot;
+                            handle_connection(stream, parser, sender);
+                        });
+                    }
+                    Err(err) => {
+                        warn!("HttpServer could not accept connection; Cause: {}", err);
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        fn stop_hook(&self) -> StopHook {
+            tcp_stop_hook(&self.stopped, self.listener.local_addr().ok())
+        }
+    }
+
+    fn handle_connection(stream: TcpStream, parser: StreamParser, sender: IpSender) {
+        let mut writer = match stream.try_clone() {
+            Ok(writer) => writer,
+            Err(e) => {
+                warn!("HttpServer could not handle connection; Cause: {}", e);
+                return;
+            }
+        };
+        let mut reader = BufReader::new(stream);
+        loop {
+            let (status, body, close) = match read_request(&mut reader) {
+                Ok(Some(request)) => {
+                    let (status, body) = handle_request(&request, parser, &sender);
+                    (status, body, request.wants_close())
+                }
+                Ok(None) => return,
+                Err(HttpError::Status(status, reason)) => (status, reason, true),
+                Err(HttpError::Io(e)) => {
+                    debug!("HttpServer connection closed; Cause: {}", e);
+                    return;
+                }
+            };
+            if write_response(&mut writer, status, &body).is_err() || close {
+                return;
+            }
+        }
+    }
+
+    fn handle_request(request: &Request, parser: StreamParser, sender: &IpSender) -> (u16, String) {
+        if request.path != "/ingest" {
+            return (404, format!("Unknown path `{}`", request.path));
+        }
+        if request.method != "POST" {
+            return (405, "Only POST is allowed".to_owned());
+        }
+        let parsed = match request.content_type().as_ref().map(String::as_str) {
+            Some("application/json") => parse_json(&request.body),
+            Some("application/octet-stream") => parse_binary(&request.body),
+            Some("text/plain") | None => parse_text(&request.body, parser),
+            Some(other) => return (415, format!("Unsupported content type `{}`", other))
+        };
+        let data = match parsed {
+            Ok(data) => data,
+            Err(e) => return (400, e)
+        };
+        let count = data.len();
+        match sender.try_send(data) {
+            Ok(()) => (202, format!("accepted {}", count)),
+            Err(TrySendError::Full(_)) => (429, "Aggregator queue is full, retry later".to_owned()),
+            Err(TrySendError::Disconnected(_)) => (503, "Aggregator is not running".to_owned())
+        }
+    }
+
+    fn parse_json(body: &[u8]) -> Result<Vec<[u8; 4]>, String> {
+        let ips = match serde_json::from_slice(body) {
+            Ok(JsonBody::List(ips)) | Ok(JsonBody::Object { ips }) => ips,
+            Err(e) => return Err(format!("Invalid JSON body; Cause: {}", e))
+        };
+        ips.iter().map(|ip| {
+            Ipv4Addr::from_str(ip).map(|addr| addr.octets()).map_err(|e| format!("Invalid address `{}`; Cause: {}", ip, e))
+        }).collect()
+    }
+
+    fn parse_text(body: &[u8], parser: StreamParser) -> Result<Vec<[u8; 4]>, String> {
+        if body.iter().all(|byte| byte.is_ascii_whitespace()) {
+            return Err("Empty body".to_owned());
+        }
+        parser(body).map_err(|e| format!("Invalid text body; Cause: {}", e))
+    }
+
+    fn parse_binary(body: &[u8]) -> Result<Vec<[u8; 4]>, String> {
+        if body.len() % 4 != 0 {
+            return Err(format!("Binary body length {} is not a multiple of 4", body.len()));
+        }
+        Ok(body.chunks(4).map(|octets| [octets[0], octets[1], octets[2], octets[3]]).collect())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use ipagg::AggEvent;
+        use parsers::simple_parser;
+        use std::io::{Read, Write};
+        use std::sync::mpsc::sync_channel;
+        use super::*;
+
+        fn post(address: &str, content_type: &str, body: &[u8]) -> String {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(stream, "POST /ingest HTTP/1.1\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                   content_type, body.len()).unwrap();
+            stream.write_all(body).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response.lines().next().unwrap().to_owned()
+        }
+
+        #[test]
+        fn test_http_server_ingest() {
+            let (tx, rx) = sync_channel(10);
+            let mut serv = HttpServer::new("127.0.0.1:12365", simple_parser, IpSender::new(tx, None)).unwrap();
+            thread::spawn(move || {
+                serv.listen().unwrap();
+            });
+
+            assert_eq!("HTTP/1.1 202 Accepted", post("127.0.0.1:12365", "text/plain", b"192.168.1.1 127.0.0.1"));
+            assert_eq!("HTTP/1.1 202 Accepted", post("127.0.0.1:12365", "application/json", br#"{"ips": ["10.0.0.1"]}"#));
+            assert_eq!("HTTP/1.1 202 Accepted", post("127.0.0.1:12365", "application/octet-stream", &[172, 16, 0, 1]));
+            assert_eq!("HTTP/1.1 400 Bad Request", post("127.0.0.1:12365", "application/json", br#"["10.0.0"]"#));
+            assert_eq!("HTTP/1.1 415 Unsupported Media Type", post("127.0.0.1:12365", "image/png", b""));
+            assert_eq!("HTTP/1.1 400 Bad Request", post("127.0.0.1:12365", "text/plain", b"10.0.0"));
+            assert_eq!("HTTP/1.1 400 Bad Request", post("127.0.0.1:12365", "text/plain", b"999.1.1.1"));
+            assert_eq!("HTTP/1.1 400 Bad Request", post("127.0.0.1:12365", "text/plain", b"hello"));
+
+            let data: Vec<Vec<[u8; 4]>> = (0..3).map(|_| {
+                match rx.recv().unwrap() {
+                    AggEvent::ADD(data, _) => data,
+                    _ => panic!("This shouldn't happened!")
+                }
+            }).collect();
+            assert_eq!(vec![vec![[192, 168, 1, 1], [127, 0, 0, 1]], vec![[10, 0, 0, 1]], vec![[172, 16, 0, 1]]], data);
+        }
+
+        #[test]
+        fn test_http_server_saturated_queue() {
+            let (tx, _rx) = sync_channel(1);
+            tx.send(AggEvent::DUMP).unwrap();
+            let mut serv = HttpServer::new("127.0.0.1:12366", simple_parser, IpSender::new(tx, None)).unwrap();
+            thread::spawn(move || {
+                serv.listen().unwrap();
+            });
+
+            assert_eq!("HTTP/1.1 429 Too Many Requests", post("127.0.0.1:12366", "text/plain", b"192.168.1.1"));
+        }
+
+        #[test]
+        fn test_http_server_connection_limit() {
+            let (tx, _rx) = sync_channel(10);
+            let mut serv = HttpServer::new("127.0.0.1:12367", simple_parser, IpSender::new(tx, None)).unwrap();
+            serv.connections = ConnectionLimit::new(1);
+            thread::spawn(move || {
+                serv.listen().unwrap();
+            });
+
+            let idle = TcpStream::connect("127.0.0.1:12367").unwrap();
+            let mut refused = TcpStream::connect("127.0.0.1:12367").unwrap();
+            let mut response = String::new();
+            refused.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+            drop(idle);
+        }
+
+        #[test]
+        fn test_parse_binary() {
+            assert_eq!(Ok(vec![[10, 0, 0, 1], [10, 0, 0, 2]]), parse_binary(&[10, 0, 0, 1, 10, 0, 0, 2]));
+            assert!(parse_binary(&[10, 0, 0]).is_err());
+        }
+    }
+}
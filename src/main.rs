@@ -1,28 +1,35 @@
+extern crate ipaggregator_rs;
 extern crate argparse;
 extern crate kafka;
 #[macro_use]
 extern crate lazy_static;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde;
 extern crate serde_yaml;
-#[macro_use]
-extern crate nom;
+extern crate serde_json;
 #[macro_use]
 extern crate log;
 extern crate log4rs;
+extern crate flate2;
+extern crate libc;
 
-mod subnet_tree;
 mod senders;
 mod config;
 mod ipagg;
-mod parsers;
 mod listeners;
-mod formatters;
+mod framing;
+mod http;
+mod oneshot;
+mod signals;
 
-use config::{load_from_default_location, load_from_file, Settings, read_cmd_line_args, default_log4rs_config};
+use ipaggregator_rs::{dump, formatters, parsers, subnet_tree};
+use config::{load_from_default_location, load_from_file, OverrideSettings, Settings, read_cmd_line_args, default_log4rs_config};
 use ipagg::IpAggregator;
+use parsers::nom_ip_parser;
 use std::env::home_dir;
 use std::path::PathBuf;
+use std::process::exit;
 
 
 lazy_static! {
@@ -38,7 +45,7 @@ lazy_static! {
         };
         if let Some(path) = overriding_settings.get_settings_path() {
             match load_from_file(&PathBuf::from(path)) {
-                Ok(settings) => return settings.override_settings(overriding_settings),
+                Ok(settings) => return apply_overrides(settings, overriding_settings),
                 Err(reason) => {
                     warn!("Can not load file: {}; Skipping ...", reason);
                 }
@@ -46,25 +53,49 @@ lazy_static! {
         }
         for path in paths {
             match load_from_default_location(&path) {
-                Ok(settings) => return settings.override_settings(overriding_settings),
+                Ok(settings) => return apply_overrides(settings, overriding_settings),
                 Err(reason) => {
                     info!("Can not load file: {}; Skipping ...", reason);
                 }
             }
         }
         info!("Using default settings...");
-        Settings::default().override_settings(overriding_settings)
+        apply_overrides(Settings::default(), overriding_settings)
     };
 }
 
+fn apply_overrides(settings: Settings, overriding_settings: OverrideSettings) -> Settings {
+    match settings.override_settings(overriding_settings) {
+        Ok(settings) => settings,
+        Err(reason) => {
+            error!("Invalid command line options; Cause: {}", reason);
+            exit(1);
+        }
+    }
+}
+
 fn main() {
-    log4rs::init_config(default_log4rs_config()).unwrap();
+    log4rs::init_config(default_log4rs_config(read_cmd_line_args().is_oneshot())).unwrap();
     if let Some(log4rs_file_path) = SETTINGS.get_logger_config() {
         info!("Initializing logger settings from file `{}`!", log4rs_file_path);
         log4rs::init_file(log4rs_file_path, Default::default()).unwrap();
     }
+    if let Some(inputs) = SETTINGS.get_oneshot_inputs() {
+        if let Err(e) = oneshot::run(&inputs, nom_ip_parser, SETTINGS.get_tree_options()) {
+            error!("One-shot aggregation failed; Cause: {}", e);
+            exit(1);
+        }
+        return;
+    }
+    if let Err(e) = signals::install_handlers() {
+        error!("Can not handle termination signals; Cause: {}", e);
+        exit(1);
+    }
     info!("Starting ipaggregator-rs");
     let mut aggregator = IpAggregator::new();
-    aggregator.start();
+    if let Err(e) = aggregator.start() {
+        error!("Graceful shutdown failed; Cause: {}", e);
+        exit(1);
+    }
     info!("Stopping ipaggregator-rs");
 }
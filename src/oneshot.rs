@@ -0,0 +1,116 @@
+use flate2::read::MultiGzDecoder;
+use formatters::make_cidr_ip_string;
+use parsers::StreamParser;
+use std::fs::File;
+use std::io::{stdin, stdout, BufRead, BufReader, BufWriter, Read, Write};
+use subnet_tree::{IPTree, TreeOptions};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Aggregates addresses read from `inputs` (`-` is stdin, gzip input is detected by its magic
+/// bytes) and writes one CIDR per line to stdout. Reads stdin when no input is given.
+pub fn run(inputs: &[String], parser: StreamParser, options: TreeOptions) -> Result<(), String> {
+    let mut tree = IPTree::with_options(options);
+    if inputs.is_empty() {
+        read_into_tree(&mut tree, open_input("-")?, parser, "-")?;
+    }
+    for input in inputs {
+        read_into_tree(&mut tree, open_input(input)?, parser, input)?;
+    }
+    let out = stdout();
+    write_tree(&tree, &mut BufWriter::new(out.lock()))
+}
+
+fn open_input(path: &str) -> Result<Box<BufRead>, String> {
+    let reader: Box<Read> = if path == "-" {
+        Box::new(stdin())
+    } else {
+        match File::open(path) {
+            Ok(file) => Box::new(file),
+            Err(e) => return Err(format!("Can not open input `{}`; Cause: {}", path, e))
+        }
+    };
+    let mut reader = BufReader::new(reader);
+    let is_gzip = match reader.fill_buf() {
+        Ok(head) => head.starts_with(&GZIP_MAGIC),
+        Err(e) => return Err(format!("Can not read input `{}`; Cause: {}", path, e))
+    };
+    if is_gzip {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+fn read_into_tree<R: BufRead>(tree: &mut IPTree, mut reader: R, parser: StreamParser, name: &str) -> Result<(), String> {
+    let mut line: Vec<u8> = Vec::new();
+    let mut line_number: usize = 0;
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) => line_number += 1,
+            Err(e) => return Err(format!("Reading of `{}` failed at line {}; Cause: {}", name, line_number + 1, e))
+        }
+        match parser(&line) {
+            Ok(octets) => octets.iter().for_each(|octet| tree.add(octet)),
+            Err(e) => warn!("Parsing of `{}` line {} failed. Skipping ...; Cause: {}", name, line_number, e)
+        }
+    }
+}
+
+/// Writes CIDRs sorted, as daemon dumps are.
+fn write_tree<W: Write>(tree: &IPTree, out: &mut W) -> Result<(), String> {
+    let mut cidrs: Vec<(u32, u8)> = tree.walk().collect();
+    cidrs.sort();
+    for cidr in cidrs {
+        if let Err(e) = writeln!(out, "{}", make_cidr_ip_string(&cidr)) {
+            return Err(format!("Writing of aggregated ranges failed; Cause: {}", e));
+        }
+    }
+    out.flush().map_err(|e| format!("Writing of aggregated ranges failed; Cause: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use parsers::nom_ip_parser;
+    use std::env::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_read_into_tree() {
+        let mut tree = IPTree::new();
+        read_into_tree(&mut tree, &b"10.0.0.1 10.0.0.2\n10.0.0.3\n"[..], nom_ip_parser, "test").unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        write_tree(&tree, &mut out).unwrap();
+        let lines: Vec<&str> = ::std::str::from_utf8(&out).unwrap().lines().collect();
+        // zeroed and broadcast addresses are added by default options
+        assert_eq!(vec!["10.0.0.0/30", "10.0.0.255/32"], lines);
+    }
+
+    #[test]
+    fn test_read_into_tree_skips_malformed_lines() {
+        let mut tree = IPTree::with_options(TreeOptions { add_zeroed: false, add_broadcast: false });
+        read_into_tree(&mut tree, &b"192.168.1.1\n10.0.0.1\n10.0.0\n999.1.1.1 10.0.0.9\n172.16.0.1 10.0.0.2\n"[..], nom_ip_parser, "test").unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        write_tree(&tree, &mut out).unwrap();
+        let lines: Vec<&str> = ::std::str::from_utf8(&out).unwrap().lines().collect();
+        assert_eq!(vec!["10.0.0.1/32", "10.0.0.2/32", "172.16.0.1/32", "192.168.1.1/32"], lines);
+    }
+
+    #[test]
+    fn test_open_gzip_input() {
+        let path = temp_dir().join("ipaggregator-test-oneshot.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"192.168.1.1\n").unwrap();
+        fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+        let mut content = String::new();
+        open_input(path.to_str().unwrap()).unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!("192.168.1.1\n", content);
+        fs::remove_file(&path).unwrap();
+    }
+}
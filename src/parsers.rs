@@ -5,32 +5,51 @@ use nom;
 
 pub type StreamParser = fn(&[u8]) -> Result<Vec<[u8; 4]>, String>;
 
+pub fn parser_by_name(name: &str) -> Result<StreamParser, String> {
+    match name {
+        "nom" => Ok(nom_ip_parser),
+        "simple" => Ok(simple_parser),
+        other => Err(format!("Unknown parser `{}` specified!", other))
+    }
+}
+
 pub fn simple_parser(bytes: &[u8]) -> Result<Vec<[u8; 4]>, String> {
     let mut from: i64 = -1;
     let mut ip_vec: Vec<[u8; 4]> = Vec::new();
     for (i, &byte) in bytes.iter().enumerate() {
-        if byte != b' ' && from < 0 {
+        let separator = byte.is_ascii_whitespace();
+        if !separator && from < 0 {
             from = i as i64;
-        } else if byte == b' ' && from >= 0 {
-            ip_vec.push(parse_ip(&bytes[from as usize..i]));
+        } else if separator && from >= 0 {
+            ip_vec.push(parse_ip(&bytes[from as usize..i])?);
             from = -1;
         }
     }
     if from >= 0 {
-        ip_vec.push(parse_ip(&bytes[from as usize..]));
+        ip_vec.push(parse_ip(&bytes[from as usize..])?);
     }
     Ok(ip_vec)
 }
 
-fn parse_ip(address_str: &[u8]) -> [u8; 4] {
-    Ipv4Addr::from_str(str::from_utf8(address_str).unwrap()).unwrap().octets()
+fn parse_ip(address_str: &[u8]) -> Result<[u8; 4], String> {
+    match str::from_utf8(address_str).ok().and_then(|address| Ipv4Addr::from_str(address).ok()) {
+        Some(address) => Ok(address.octets()),
+        None => Err(format!("`{}` is not an IPv4 address", String::from_utf8_lossy(address_str)))
+    }
 }
 
-named!(nom_parse_ip<&[u8], Vec<[u8; 4]>>, many0!(ws!(map!(is_a!("0123456789."), parse_ip) )));
+named!(nom_parse_ip<&[u8], Vec<[u8; 4]>>, many0!(ws!(map_res!(is_a!("0123456789."), parse_ip) )));
 
+/// Fails on the first token which is not an IPv4 address.
 pub fn nom_ip_parser(stream: &[u8]) -> Result<Vec<[u8; 4]>, String> {
     match nom_parse_ip(stream) {
-        nom::IResult::Done(_, octets) => Ok(octets),
+        nom::IResult::Done(rest, octets) => {
+            if rest.iter().all(|byte| byte.is_ascii_whitespace()) {
+                Ok(octets)
+            } else {
+                Err(format!("Unexpected input `{}`", String::from_utf8_lossy(rest)))
+            }
+        },
         nom::IResult::Error(e) => Err(format!("Error occurred during parsing: {}", e)),
         nom::IResult::Incomplete(_) => Err("Octet stream is incomplete".to_owned())
     }
@@ -43,8 +62,20 @@ mod tests {
 
     #[test]
     fn test_parse_ip() {
-        assert_eq!([192, 168, 1, 1], parse_ip(b"192.168.1.1"));
-        assert_eq!([127, 0, 0, 1], parse_ip(b"127.0.0.1"));
+        assert_eq!(Ok([192, 168, 1, 1]), parse_ip(b"192.168.1.1"));
+        assert_eq!(Ok([127, 0, 0, 1]), parse_ip(b"127.0.0.1"));
+        assert!(parse_ip(b"10.0.0").is_err());
+        assert!(parse_ip(b"999.1.1.1").is_err());
+    }
+
+    #[test]
+    fn test_parsers_reject_malformed_input() {
+        for parser in &[simple_parser as StreamParser, nom_ip_parser] {
+            assert!(parser(b"10.0.0.1 10.0.0\n").is_err());
+            assert!(parser(b"999.1.1.1\n").is_err());
+            assert!(parser(b"hello\n").is_err());
+            assert_eq!(Ok(vec![[10, 0, 0, 1], [10, 0, 0, 2]]), parser(b"10.0.0.1\t10.0.0.2\n"));
+        }
     }
 
     #[test]
@@ -53,6 +84,12 @@ mod tests {
         assert_eq!(Ok(vec![[127, 0, 0, 1], [192, 168, 1, 1]]), simple_parser(ips));
     }
 
+    #[test]
+    fn test_parser_by_name() {
+        assert_eq!(Ok(vec![[127, 0, 0, 1]]), parser_by_name("simple").unwrap()(b"127.0.0.1"));
+        assert!(parser_by_name("regex").is_err());
+    }
+
     #[test]
     fn test_nom_ip_parser() {
         let ips = b" 127.0.0.1  192.168.1.1 ";
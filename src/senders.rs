@@ -1,28 +1,114 @@
-use formatters::AggFormatter;
-use std::net::UdpSocket;
+use dump::DumpEvent;
+use formatters::{formatter_by_name, Formatter};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
 use std::sync::mpsc::Receiver;
 use std::time::Duration;
-use config::Settings;
+use config::Senders;
+use framing::Framing;
+use http::{parse_url, Url};
 
 pub enum PublisherCredentials {
-    Udp(String),
-    Kafka(Vec<String>, Duration, String),
+    /// Destination, bind address and TTL of IPv4 multicast.
+    Udp(String, Option<String>, Option<u32>),
+    Tcp(String, tcp::TcpPublisherOptions),
+    /// Path template, number of files to keep and whether to gzip them.
+    File(String, usize, bool),
+    Webhook(Url, webhook::WebhookOptions),
+    Kafka(Vec<String>, Duration, String, kafka::KafkaProducerOptions),
 }
 
 
-pub fn get_publisher_credentials(settings: &Settings) -> Result<PublisherCredentials, String> {
+/// Creates formatter configured for the sink.
+pub fn formatter_from_settings(settings: &Senders) -> Result<Box<Formatter>, String> {
+    formatter_by_name(settings.get_formatter(), settings.get_format_options())
+}
+
+/// Formatters ignoring `max_size` do not fit publishers with a message size limit.
+fn reject_unbounded_formatter(settings: &Senders, formatter: &Formatter) -> Result<(), String> {
+    if formatter.bounded() {
+        return Ok(());
+    }
+    Err(format!("Formatter `{}` exceeds message size of `{}` publisher, use `ndjson` instead",
+                settings.get_formatter(), settings.get_publisher_type()))
+}
+
+/// Publishers delimiting messages by newlines can not carry binary formatter output.
+fn reject_binary_formatter(settings: &Senders, formatter: &Formatter) -> Result<(), String> {
+    if !formatter.binary() {
+        return Ok(());
+    }
+    Err(format!("Formatter `{}` can not be used with newline delimited `{}` publisher",
+                settings.get_formatter(), settings.get_publisher_type()))
+}
+
+pub fn get_publisher_credentials(settings: &Senders) -> Result<PublisherCredentials, String> {
+    let formatter = formatter_from_settings(settings)?;
     match settings.get_publisher_type().as_ref() {
         "udp" => {
+            reject_unbounded_formatter(settings, &*formatter)?;
             match settings.get_udp_send_to() {
-                Some(ref address) => Ok(PublisherCredentials::Udp(address.to_owned())),
+                Some(_) if settings.get_max_datagram_size() > MAX_UDP_PAYLOAD_SIZE => {
+                    Err(format!("Datagram size {} exceeds maximal UDP payload {}", settings.get_max_datagram_size(), MAX_UDP_PAYLOAD_SIZE))
+                }
+                Some(ref address) => Ok(PublisherCredentials::Udp(address.to_owned(),
+                                                                  settings.get_udp_bind_address(),
+                                                                  settings.get_multicast_ttl())),
                 None => Err("Expected udp publisher, but couldn't find `send_to` address".to_owned())
             }
         },
+        "tcp" => {
+            match settings.get_tcp_publisher_settings() {
+                Some(ref tcp) => {
+                    let framing = Framing::from_name(tcp.get_framing())?;
+                    if framing == Framing::Newline && !formatter.single_line() {
+                        return Err(format!("Formatter `{}` produces multi-line messages, use `length_prefixed` framing",
+                                           settings.get_formatter()));
+                    }
+                    Ok(PublisherCredentials::Tcp(tcp.get_address(), tcp::TcpPublisherOptions {
+                        framing,
+                        buffer_dumps: tcp.get_buffer_dumps(),
+                        min_backoff: Duration::from_millis(tcp.get_reconnect_min_ms()),
+                        max_backoff: Duration::from_millis(tcp.get_reconnect_max_ms()),
+                    }))
+                },
+                None => Err("Expected tcp publisher, but couldn't find tcp publisher config".to_owned())
+            }
+        },
+        "file" => {
+            reject_binary_formatter(settings, &*formatter)?;
+            match settings.get_file_publisher_settings() {
+                Some(ref file) if file.get_keep() == 0 => Err("File publisher has to keep at least one file".to_owned()),
+                Some(ref file) => Ok(PublisherCredentials::File(file.get_path(), file.get_keep(), file.is_compress())),
+                None => Err("Expected file publisher, but couldn't find file publisher config".to_owned())
+            }
+        },
+        "webhook" => {
+            match settings.get_webhook_publisher_settings() {
+                Some(ref webhook) => {
+                    let options = webhook::WebhookOptions::from_settings(webhook)?;
+                    if options.body == webhook::WebhookBody::Text {
+                        reject_binary_formatter(settings, &*formatter)?;
+                    }
+                    Ok(PublisherCredentials::Webhook(parse_url(webhook.get_url())?, options))
+                },
+                None => Err("Expected webhook publisher, but couldn't find webhook publisher config".to_owned())
+            }
+        },
         "kafka" => {
+            reject_unbounded_formatter(settings, &*formatter)?;
             match settings.get_kafka_publisher_credentials() {
-                Some(ref credentials) => Ok(PublisherCredentials::Kafka(credentials.get_hosts(),
-                                                                        Duration::new(credentials.get_ack_duration_seconds(), 0),
-                                                                        credentials.get_topic())),
+                Some(ref credentials) => {
+                    let options = kafka::KafkaProducerOptions::from_settings(credentials)?;
+                    // keyed records are formatted group by group, which breaks state carried over between chunks
+                    if options.key != kafka::MessageKey::None && formatter.stateful() {
+                        return Err(format!("Formatter `{}` can not be used with kafka message key `{}`",
+                                           settings.get_formatter(), credentials.get_message_key()));
+                    }
+                    Ok(PublisherCredentials::Kafka(credentials.get_hosts(),
+                                                   Duration::new(credentials.get_ack_duration_seconds(), 0),
+                                                   credentials.get_topic(),
+                                                   options))
+                },
                 None => Err("Expected kafka publisher, but couldn't find kafka publisher config".to_owned())
             }
         }
@@ -32,18 +118,27 @@ pub fn get_publisher_credentials(settings: &Settings) -> Result<PublisherCredent
 
 
 pub fn create_publisher(credentials: PublisherCredentials,
-                        formatter: AggFormatter,
-                        receiver: Receiver<Vec<(u32, u8)>>)
+                        formatter: Box<Formatter>,
+                        receiver: Receiver<DumpEvent>)
                         -> Result<Box<Publisher + 'static>, String> {
     match credentials {
-        PublisherCredentials::Udp(host) => {
-            match UdpSender::new(host.as_ref(), formatter, receiver) {
+        PublisherCredentials::Udp(host, bind_address, multicast_ttl) => {
+            match UdpSender::new(host.as_ref(), bind_address, multicast_ttl, formatter, receiver) {
                 Ok(sender) => Ok(Box::new(sender)),
                 Err(e) => Err(e)
             }
         }
-        PublisherCredentials::Kafka(hosts, ack_timeout, topic) => {
-            match kafka::KafkaProducer::new(hosts, ack_timeout, topic, formatter, receiver) {
+        PublisherCredentials::Tcp(address, options) => {
+            Ok(Box::new(tcp::TcpPublisher::new(address, options, formatter, receiver)))
+        }
+        PublisherCredentials::File(template, keep, compress) => {
+            Ok(Box::new(file::FilePublisher::new(template, keep, compress, formatter, receiver)))
+        }
+        PublisherCredentials::Webhook(url, options) => {
+            Ok(Box::new(webhook::WebhookPublisher::new(url, options, formatter, receiver)))
+        }
+        PublisherCredentials::Kafka(hosts, ack_timeout, topic, options) => {
+            match kafka::KafkaProducer::new(hosts, ack_timeout, topic, options, formatter, receiver) {
                 Ok(publisher) => Ok(Box::new(publisher)),
                 Err(e) => Err(e)
             }
@@ -56,76 +151,634 @@ pub trait Publisher {
 }
 
 
+/// Largest payload of a single IPv4 UDP datagram.
+pub const MAX_UDP_PAYLOAD_SIZE: usize = 65507;
+
 pub struct UdpSender {
     socket: UdpSocket,
-    receiver: Receiver<Vec<(u32, u8)>>,
-    formatter: AggFormatter,
-    send_to: String,
+    receiver: Receiver<DumpEvent>,
+    formatter: Box<Formatter>,
+    send_to: SocketAddr,
 }
 
 
 impl UdpSender {
-    pub fn new(send_to: &str, formatter: AggFormatter, receiver: Receiver<Vec<(u32, u8)>>) -> Result<UdpSender, String> {
-        match UdpSocket::bind("127.0.0.1:43211") {
-            Ok(socket) => {
-                Ok(UdpSender { socket, receiver, formatter, send_to: send_to.to_string() })
+    /// Binds to `bind_address`, or to the unspecified address of destination's family with
+    /// port chosen by OS when none is given. `multicast_ttl` is applied to IPv4 multicast destinations.
+    pub fn new(send_to: &str, bind_address: Option<String>, multicast_ttl: Option<u32>,
+               formatter: Box<Formatter>, receiver: Receiver<DumpEvent>) -> Result<UdpSender, String> {
+        let send_to = match send_to.to_socket_addrs().map(|mut addresses| addresses.next()) {
+            Ok(Some(address)) => address,
+            Ok(None) => return Err(format!("Address `{}` did not resolve", send_to)),
+            Err(e) => return Err(format!("Can not resolve `{}`; Cause: {}", send_to, e))
+        };
+        let bind_address = bind_address.unwrap_or_else(|| {
+            match send_to {
+                SocketAddr::V4(_) => "0.0.0.0:0".to_owned(),
+                SocketAddr::V6(_) => "[::]:0".to_owned(),
+            }
+        });
+        let socket = match UdpSocket::bind(bind_address.as_str()) {
+            Ok(socket) => socket,
+            Err(reason) => return Err(format!("Can not bind sender to address `{}`: {}", bind_address, reason))
+        };
+        if send_to.ip().is_multicast() {
+            match (send_to, multicast_ttl) {
+                (SocketAddr::V4(_), Some(ttl)) => {
+                    if let Err(e) = socket.set_multicast_ttl_v4(ttl) {
+                        return Err(format!("Can not set multicast TTL {}; Cause: {}", ttl, e));
+                    }
+                }
+                (SocketAddr::V6(_), Some(_)) => warn!("Multicast TTL is applied only to IPv4 destinations, `{}` uses default hop limit", send_to),
+                (_, None) => {}
             }
-            Err(reason) => Err(format!("Can not bind sender to address: {}", reason))
         }
+        Ok(UdpSender { socket, receiver, formatter, send_to })
     }
 }
 
 impl Publisher for UdpSender {
+    /// Sends formatted dumps until the dump channel is closed.
     fn run_sender(&mut self) {
-        loop {
-            match self.receiver.recv() {
-                Ok(cidr_vec) => {
-                    if cidr_vec == vec![(0, 33)] {
-                        break;
+        for event in self.receiver.iter() {
+            for message in self.formatter.format(&event) {
+                if let Err(e) = self.socket.send_to(&message, self.send_to) {
+                    error!("UdpSender failed to send {} bytes to `{}`; Cause: {}", message.len(), self.send_to, e);
+                }
+            }
+        }
+    }
+}
+
+pub mod tcp {
+    use super::*;
+    use std::cmp;
+    use std::collections::VecDeque;
+    use std::io;
+    use std::io::{BufWriter, Write};
+    use std::mem;
+    use std::net::TcpStream;
+    use std::sync::mpsc::RecvTimeoutError;
+    use std::time::Instant;
+
+    const WRITE_TIMEOUT: u64 = 10;
+
+    pub struct TcpPublisherOptions {
+        pub framing: Framing,
+        /// Complete dumps kept while endpoint is unreachable; oldest ones are dropped first.
+        pub buffer_dumps: usize,
+        pub min_backoff: Duration,
+        pub max_backoff: Duration,
+    }
+
+    /// Sends every formatted message as one frame. Dumps are queued once their end marker
+    /// arrives and written out whole; a dump interrupted by a lost connection is sent again
+    /// after reconnect, so receiver should discard dumps without an end marker.
+    pub struct TcpPublisher {
+        address: String,
+        options: TcpPublisherOptions,
+        formatter: Box<Formatter>,
+        receiver: Receiver<DumpEvent>,
+        stream: Option<BufWriter<TcpStream>>,
+        current: Vec<Vec<u8>>,
+        pending: VecDeque<Vec<Vec<u8>>>,
+        backoff: Duration,
+        next_attempt: Instant,
+    }
+
+    impl TcpPublisher {
+        pub fn new(address: String, options: TcpPublisherOptions, formatter: Box<Formatter>,
+                   receiver: Receiver<DumpEvent>) -> TcpPublisher {
+            let backoff = options.min_backoff;
+            TcpPublisher {
+                address, options, formatter, receiver,
+                stream: None,
+                current: Vec::new(),
+                pending: VecDeque::new(),
+                backoff,
+                next_attempt: Instant::now(),
+            }
+        }
+
+        fn handle_event(&mut self, event: &DumpEvent) {
+            if let DumpEvent::Begin(_) = *event {
+                if !self.current.is_empty() {
+                    warn!("TcpPublisher for `{}` did not receive end of previous dump; Discarding it", self.address);
+                    self.current.clear();
+                }
+            }
+            self.current.extend(self.formatter.format(event));
+            if let DumpEvent::End(_) = *event {
+                let dump = mem::replace(&mut self.current, Vec::new());
+                self.pending.push_back(dump);
+                if self.pending.len() > self.options.buffer_dumps {
+                    warn!("TcpPublisher buffer for `{}` is full; Dropping oldest dump", self.address);
+                    self.pending.pop_front();
+                }
+            }
+        }
+
+        fn connect(&mut self) -> bool {
+            if self.stream.is_some() {
+                return true;
+            }
+            if Instant::now() < self.next_attempt {
+                return false;
+            }
+            match TcpStream::connect(self.address.as_str()) {
+                Ok(stream) => {
+                    if let Err(e) = stream.set_write_timeout(Some(Duration::from_secs(WRITE_TIMEOUT))) {
+                        warn!("Can not set write timeout of `{}`; Cause: {}", self.address, e);
                     }
-                    for ip_string in (self.formatter)(cidr_vec) {
-                        self.socket.send_to(ip_string.as_bytes(), self.send_to.as_str()).unwrap();
+                    info!("TcpPublisher connected to `{}`", self.address);
+                    self.stream = Some(BufWriter::new(stream));
+                    self.backoff = self.options.min_backoff;
+                    true
+                }
+                Err(e) => {
+                    warn!("TcpPublisher can not connect to `{}`, retrying in {:?}; Cause: {}", self.address, self.backoff, e);
+                    self.schedule_reconnect();
+                    false
+                }
+            }
+        }
+
+        fn schedule_reconnect(&mut self) {
+            self.stream = None;
+            self.next_attempt = Instant::now() + self.backoff;
+            self.backoff = cmp::min(self.backoff * 2, self.options.max_backoff);
+        }
+
+        fn send_pending(&mut self) {
+            while !self.pending.is_empty() && self.connect() {
+                let result = match self.stream {
+                    Some(ref mut stream) => write_dump(stream, self.options.framing, &self.pending[0]),
+                    None => continue
+                };
+                match result {
+                    Ok(()) => {
+                        self.pending.pop_front();
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::InvalidInput => {
+                        error!("TcpPublisher can not frame dump for `{}`; Dropping it; Cause: {}", self.address, e);
+                        self.pending.pop_front();
+                    }
+                    Err(e) => {
+                        warn!("TcpPublisher lost connection to `{}`, retrying in {:?}; Cause: {}", self.address, self.backoff, e);
+                        self.schedule_reconnect();
                     }
                 }
-                Err(reason) => panic!("UdpSender::run_sender panicked; Cause: {}", reason)
             }
-        };
+        }
+    }
+
+    fn write_dump<W: Write>(writer: &mut W, framing: Framing, dump: &[Vec<u8>]) -> io::Result<()> {
+        for message in dump {
+            framing.write_frame(writer, message)?;
+        }
+        writer.flush()
+    }
+
+    impl Publisher for TcpPublisher {
+        /// Sends dumps until the dump channel is closed. While dumps are pending, waits for
+        /// next event at most until next reconnect attempt.
+        fn run_sender(&mut self) {
+            loop {
+                let event = if self.pending.is_empty() {
+                    match self.receiver.recv() {
+                        Ok(event) => Some(event),
+                        Err(_) => break
+                    }
+                } else {
+                    let now = Instant::now();
+                    let wait = if self.next_attempt > now { self.next_attempt - now } else { Duration::from_millis(0) };
+                    match self.receiver.recv_timeout(wait) {
+                        Ok(event) => Some(event),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => break
+                    }
+                };
+                if let Some(ref event) = event {
+                    self.handle_event(event);
+                }
+                self.send_pending();
+            }
+            self.send_pending();
+            if !self.pending.is_empty() {
+                warn!("TcpPublisher for `{}` stopped with {} unsent dumps", self.address, self.pending.len());
+            }
+        }
+    }
+}
+
+pub mod file {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use dump::DumpInfo;
+    use std::collections::VecDeque;
+    use std::fs;
+    use std::fs::File;
+    use std::io;
+    use std::io::{BufWriter, Write};
+    use std::mem;
+
+    /// Writes every dump into its own file named by rendering the path template. Dump is
+    /// written to `<path>.tmp` first and renamed once complete, so readers never see a partial
+    /// file. Only the last `keep` files written by this publisher are kept; files written before
+    /// a restart are not tracked and have to be removed externally.
+    pub struct FilePublisher {
+        template: String,
+        keep: usize,
+        compress: bool,
+        formatter: Box<Formatter>,
+        receiver: Receiver<DumpEvent>,
+        current: Vec<Vec<u8>>,
+        written: VecDeque<String>,
+    }
+
+    /// Substitutes `{timestamp}` and `{sequence}` in `template` with values of the dump.
+    pub fn render_path(template: &str, info: &DumpInfo) -> String {
+        template.replace("{timestamp}", &info.timestamp.to_string())
+            .replace("{sequence}", &info.sequence.to_string())
+    }
+
+    impl FilePublisher {
+        pub fn new(template: String, keep: usize, compress: bool, formatter: Box<Formatter>,
+                   receiver: Receiver<DumpEvent>) -> FilePublisher {
+            FilePublisher { template, keep, compress, formatter, receiver, current: Vec::new(), written: VecDeque::new() }
+        }
+
+        fn write_dump(&self, path: &str, dump: &[Vec<u8>]) -> io::Result<()> {
+            let tmp_path = format!("{}.tmp", path);
+            let file = File::create(&tmp_path)?;
+            let file = if self.compress {
+                let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+                write_lines(&mut encoder, dump)?;
+                encoder.finish()?.into_inner().map_err(|e| e.into_error())?
+            } else {
+                let mut writer = BufWriter::new(file);
+                write_lines(&mut writer, dump)?;
+                writer.into_inner().map_err(|e| e.into_error())?
+            };
+            file.sync_all()?;
+            fs::rename(&tmp_path, path)
+        }
+
+        fn remove_old_files(&mut self) {
+            while self.written.len() > self.keep {
+                if let Some(path) = self.written.pop_front() {
+                    if let Err(e) = fs::remove_file(&path) {
+                        warn!("Can not remove old dump `{}`; Cause: {}", path, e);
+                    }
+                }
+            }
+        }
+    }
+
+    fn write_lines<W: Write>(writer: &mut W, lines: &[Vec<u8>]) -> io::Result<()> {
+        for line in lines {
+            writer.write_all(line)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    impl Publisher for FilePublisher {
+        /// Writes dumps until the dump channel is closed.
+        fn run_sender(&mut self) {
+            while let Ok(event) = self.receiver.recv() {
+                if let DumpEvent::Begin(_) = event {
+                    self.current.clear();
+                }
+                self.current.extend(self.formatter.format(&event));
+                if let DumpEvent::End(ref info) = event {
+                    let path = render_path(&self.template, info);
+                    let dump = mem::replace(&mut self.current, Vec::new());
+                    match self.write_dump(&path, &dump) {
+                        Ok(()) => {
+                            if self.written.back() != Some(&path) {
+                                self.written.push_back(path);
+                            }
+                            self.remove_old_files();
+                        }
+                        Err(e) => error!("FilePublisher failed to write dump {} to `{}`; Cause: {}", info.sequence, path, e)
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub mod webhook {
+    use super::*;
+    use config::WebhookSender;
+    use formatters::make_cidr_ip_string;
+    use http::post;
+    use dump::DumpInfo;
+    use serde_json;
+    use std::collections::BTreeSet;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::mem;
+    use std::thread;
+
+    #[derive(PartialEq, Eq, Debug, Clone, Copy)]
+    pub enum WebhookBody {
+        /// JSON object with dump info and list of prefixes.
+        Json,
+        /// Formatter output, one message per line; `+prefix` / `-prefix` lines in delta mode.
+        Text,
+    }
+
+    #[derive(PartialEq, Eq, Debug, Clone, Copy)]
+    pub enum WebhookMode {
+        Full,
+        /// Only prefixes added and removed since the last delivered dump.
+        Delta,
+    }
+
+    pub struct WebhookOptions {
+        pub headers: Vec<(String, String)>,
+        pub body: WebhookBody,
+        pub mode: WebhookMode,
+        pub retries: u32,
+        pub retry_backoff: Duration,
+        pub timeout: Duration,
+        pub dead_letter: Option<String>,
+    }
+
+    impl WebhookOptions {
+        pub fn from_settings(settings: &WebhookSender) -> Result<WebhookOptions, String> {
+            let body = match settings.get_body() {
+                "json" => WebhookBody::Json,
+                "text" => WebhookBody::Text,
+                other => return Err(format!("Unknown webhook body `{}` specified!", other))
+            };
+            let mode = match settings.get_mode() {
+                "full" => WebhookMode::Full,
+                "delta" => WebhookMode::Delta,
+                other => return Err(format!("Unknown webhook mode `{}` specified!", other))
+            };
+            Ok(WebhookOptions {
+                headers: settings.get_headers(),
+                body,
+                mode,
+                retries: settings.get_retries(),
+                retry_backoff: Duration::from_millis(settings.get_retry_backoff_ms()),
+                timeout: Duration::from_millis(settings.get_timeout_ms()),
+                dead_letter: settings.get_dead_letter(),
+            })
+        }
+    }
+
+    #[derive(Serialize)]
+    struct JsonDump {
+        sequence: u64,
+        timestamp: u64,
+        total: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        prefixes: Option<Vec<String>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        added: Option<Vec<String>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        removed: Option<Vec<String>>,
+    }
+
+    /// POSTs every complete dump to the configured URL. Failed requests are retried with
+    /// exponential backoff; body of a dump that could not be delivered is appended to the
+    /// dead letter file.
+    pub struct WebhookPublisher {
+        url: Url,
+        options: WebhookOptions,
+        formatter: Box<Formatter>,
+        receiver: Receiver<DumpEvent>,
+        messages: Vec<Vec<u8>>,
+        current: BTreeSet<(u32, u8)>,
+        delivered: BTreeSet<(u32, u8)>,
+    }
+
+    fn cidr_strings<'a, I: Iterator<Item=&'a (u32, u8)>>(cidrs: I) -> Vec<String> {
+        cidrs.map(make_cidr_ip_string).collect()
+    }
+
+    /// Builds request body of a dump; in delta mode relative to the `delivered` prefixes.
+    pub fn make_body(body: WebhookBody, mode: WebhookMode, info: &DumpInfo, messages: &[Vec<u8>],
+                     current: &BTreeSet<(u32, u8)>, delivered: &BTreeSet<(u32, u8)>) -> Vec<u8> {
+        match (body, mode) {
+            (WebhookBody::Json, WebhookMode::Full) => {
+                let dump = JsonDump { sequence: info.sequence, timestamp: info.timestamp, total: info.total,
+                    prefixes: Some(cidr_strings(current.iter())), added: None, removed: None };
+                serde_json::to_vec(&dump).unwrap_or_default()
+            }
+            (WebhookBody::Json, WebhookMode::Delta) => {
+                let dump = JsonDump { sequence: info.sequence, timestamp: info.timestamp, total: info.total,
+                    prefixes: None,
+                    added: Some(cidr_strings(current.difference(delivered))),
+                    removed: Some(cidr_strings(delivered.difference(current))) };
+                serde_json::to_vec(&dump).unwrap_or_default()
+            }
+            (WebhookBody::Text, WebhookMode::Full) => {
+                messages.iter().flat_map(|message| message.iter().cloned().chain(Some(b'\n'))).collect()
+            }
+            (WebhookBody::Text, WebhookMode::Delta) => {
+                let added = current.difference(delivered).map(|cidr| format!("+{}\n", make_cidr_ip_string(cidr)));
+                let removed = delivered.difference(current).map(|cidr| format!("-{}\n", make_cidr_ip_string(cidr)));
+                added.chain(removed).collect::<String>().into_bytes()
+            }
+        }
+    }
+
+    impl WebhookPublisher {
+        pub fn new(url: Url, options: WebhookOptions, formatter: Box<Formatter>, receiver: Receiver<DumpEvent>) -> WebhookPublisher {
+            WebhookPublisher { url, options, formatter, receiver,
+                messages: Vec::new(), current: BTreeSet::new(), delivered: BTreeSet::new() }
+        }
+
+        fn content_type(&self) -> &'static str {
+            match self.options.body {
+                WebhookBody::Json => "application/json",
+                WebhookBody::Text => "text/plain",
+            }
+        }
+
+        fn deliver(&self, sequence: u64, body: &[u8]) -> bool {
+            let mut backoff = self.options.retry_backoff;
+            for attempt in 0..self.options.retries + 1 {
+                if attempt > 0 {
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                }
+                match post(&self.url, &self.options.headers, self.content_type(), body, self.options.timeout) {
+                    Ok(status) if status >= 200 && status < 300 => return true,
+                    Ok(status) => warn!("Webhook `{}` answered {} to dump {}", self.url.authority, status, sequence),
+                    Err(e) => warn!("Webhook `{}` failed for dump {}; Cause: {}", self.url.authority, sequence, e)
+                }
+            }
+            false
+        }
+
+        fn write_dead_letter(&self, path: &str, body: &[u8]) -> Result<(), String> {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)
+                .map_err(|e| format!("Can not open dead letter file `{}`; Cause: {}", path, e))?;
+            file.write_all(body).and_then(|_| file.write_all(b"\n"))
+                .map_err(|e| format!("Can not write dead letter file `{}`; Cause: {}", path, e))
+        }
+
+        fn publish(&mut self, info: &DumpInfo) {
+            let body = make_body(self.options.body, self.options.mode, info, &self.messages, &self.current, &self.delivered);
+            self.messages.clear();
+            let current = mem::replace(&mut self.current, BTreeSet::new());
+            if self.deliver(info.sequence, &body) {
+                self.delivered = current;
+                return;
+            }
+            error!("Webhook `{}` did not accept dump {} after {} retries", self.url.authority, info.sequence, self.options.retries);
+            if let Some(ref path) = self.options.dead_letter {
+                if let Err(e) = self.write_dead_letter(path, &body) {
+                    error!("{}", e);
+                }
+            }
+        }
+    }
+
+    impl Publisher for WebhookPublisher {
+        /// Publishes dumps until the dump channel is closed.
+        fn run_sender(&mut self) {
+            while let Ok(event) = self.receiver.recv() {
+                match event {
+                    DumpEvent::Begin(_) => {
+                        self.messages.clear();
+                        self.current.clear();
+                    }
+                    DumpEvent::Chunk(_, ref cidrs) => self.current.extend(cidrs.iter().cloned()),
+                    DumpEvent::End(_) => {}
+                }
+                self.messages.extend(self.formatter.format(&event));
+                if let DumpEvent::End(ref info) = event {
+                    self.publish(info);
+                }
+            }
+        }
     }
 }
 
 pub mod kafka {
     use super::*;
-    use kafka::producer::{Producer, Record, RequiredAcks};
+    use config::KafkaSender;
+    use formatters::make_cidr_ip_string;
+    use kafka::producer::{Compression, Producer, Record, RequiredAcks};
+    use std::collections::BTreeMap;
+
+    /// How records are keyed, so that compacted topics keep the latest value per key.
+    #[derive(PartialEq, Eq, Debug, Clone, Copy)]
+    pub enum MessageKey {
+        None,
+        /// Prefixes are grouped by their first octet and keyed by it, e.g. `10`.
+        FirstOctet,
+        /// Every prefix is sent as its own record keyed by the prefix, e.g. `10.0.0.0/8`.
+        Prefix,
+    }
+
+    pub struct KafkaProducerOptions {
+        pub required_acks: RequiredAcks,
+        pub compression: Compression,
+        pub key: MessageKey,
+        pub batch: bool,
+    }
+
+    impl KafkaProducerOptions {
+        pub fn from_settings(settings: &KafkaSender) -> Result<KafkaProducerOptions, String> {
+            let required_acks = match settings.get_required_acks() {
+                "none" => RequiredAcks::None,
+                "one" => RequiredAcks::One,
+                "all" => RequiredAcks::All,
+                other => return Err(format!("Unknown kafka required acks `{}` specified!", other))
+            };
+            let compression = match settings.get_compression() {
+                "none" => Compression::NONE,
+                "gzip" => Compression::GZIP,
+                "snappy" => Compression::SNAPPY,
+                other => return Err(format!("Unknown kafka compression `{}` specified!", other))
+            };
+            let key = match settings.get_message_key() {
+                "none" => MessageKey::None,
+                "first_octet" => MessageKey::FirstOctet,
+                "prefix" => MessageKey::Prefix,
+                other => return Err(format!("Unknown kafka message key `{}` specified!", other))
+            };
+            Ok(KafkaProducerOptions { required_acks, compression, key, batch: settings.is_batch() })
+        }
+    }
 
     pub struct KafkaProducer {
         producer: Producer,
-        formatter: AggFormatter,
-        receiver: Receiver<Vec<(u32, u8)>>,
+        formatter: Box<Formatter>,
+        receiver: Receiver<DumpEvent>,
         topic: String,
+        key: MessageKey,
+        batch: bool,
     }
 
     impl KafkaProducer {
-        pub fn new(hosts: Vec<String>, ack_timeout: Duration, topic: String, formatter: AggFormatter, receiver: Receiver<Vec<(u32, u8)>>)
+        pub fn new(hosts: Vec<String>, ack_timeout: Duration, topic: String, options: KafkaProducerOptions,
+                   formatter: Box<Formatter>, receiver: Receiver<DumpEvent>)
                    -> Result<KafkaProducer, String> {
-            match Producer::from_hosts(hosts).with_ack_timeout(ack_timeout).with_required_acks(RequiredAcks::One).create() {
+            match Producer::from_hosts(hosts)
+                .with_ack_timeout(ack_timeout)
+                .with_required_acks(options.required_acks)
+                .with_compression(options.compression)
+                .create() {
                 Ok(producer) => {
-                    Ok(KafkaProducer { producer, formatter, receiver, topic })
+                    Ok(KafkaProducer { producer, formatter, receiver, topic, key: options.key, batch: options.batch })
                 }
                 Err(e) => Err(format!("Creation of new KafkaProducer failed; Reason: {}", e))
             }
         }
+
+        fn send_messages(producer: &mut Producer, topic: &str, batch: bool, messages: &[(String, Vec<u8>)]) -> Result<(), String> {
+            let records: Vec<Record<&[u8], &[u8]>> = messages.iter().map(|&(ref key, ref value)| {
+                Record::from_key_value(topic, key.as_bytes(), value.as_slice())
+            }).collect();
+            if batch {
+                producer.send_all(&records).map(|_| ()).map_err(|e| e.to_string())
+            } else {
+                records.iter().map(|record| producer.send(record).map_err(|e| e.to_string())).collect()
+            }
+        }
+    }
+
+    /// Formats a dump event into `(key, value)` messages; empty key means unkeyed record.
+    /// Dump markers are never keyed.
+    pub fn keyed_messages(key: MessageKey, formatter: &mut Formatter, event: &DumpEvent) -> Vec<(String, Vec<u8>)> {
+        match (key, event) {
+            (MessageKey::FirstOctet, &DumpEvent::Chunk(offset, ref cidrs)) => {
+                let mut groups: BTreeMap<u32, Vec<(u32, u8)>> = BTreeMap::new();
+                for cidr in cidrs {
+                    groups.entry(cidr.0 >> 24).or_insert_with(Vec::new).push(*cidr);
+                }
+                groups.into_iter().flat_map(|(octet, cidrs)| {
+                    formatter.chunk(offset, &cidrs).into_iter().map(move |value| (octet.to_string(), value))
+                }).collect()
+            }
+            (MessageKey::Prefix, &DumpEvent::Chunk(offset, ref cidrs)) => {
+                cidrs.iter().enumerate().flat_map(|(idx, cidr)| {
+                    let key = make_cidr_ip_string(cidr);
+                    formatter.chunk(offset + idx, &[*cidr]).into_iter().map(move |value| (key.clone(), value))
+                }).collect()
+            }
+            _ => formatter.format(event).into_iter().map(|value| (String::new(), value)).collect()
+        }
     }
 
     impl Publisher for KafkaProducer {
+        /// Sends records of every event as it arrives, a chunk in one produce request when batching,
+        /// until the dump channel is closed.
         fn run_sender(&mut self) {
-            loop {
-                match self.receiver.recv() {
-                    Ok(ip_vec) => {
-                        for ip_string in (self.formatter)(ip_vec) {
-                            self.producer.send(&Record::from_value(self.topic.as_ref(), ip_string.as_bytes())).unwrap();
-                        }
-                    }
-                    Err(e) => panic!("KafkaProducer::run_sender panicked; Cause: {}", e)
+            for event in self.receiver.iter() {
+                let messages = keyed_messages(self.key, &mut *self.formatter, &event);
+                if let Err(e) = KafkaProducer::send_messages(&mut self.producer, &self.topic, self.batch, &messages) {
+                    error!("KafkaProducer failed to send {} records; Cause: {}", messages.len(), e);
                 }
             }
         }
@@ -136,11 +789,18 @@ pub mod kafka {
 mod tests {
     use super::*;
     use std::str;
+    use serde_yaml;
+    use formatters::{make_cidr_ip_string, FormatOptions, TextFormatter};
+    use dump::DumpInfo;
 
     fn make_prefix(octets: [u8; 4]) -> u32 {
         (octets[0] as u32) << 24 | (octets[1] as u32) << 16 | (octets[2] as u32) << 8 | octets[3] as u32
     }
 
+    fn senders(yaml: &str) -> Senders {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
     #[test]
     fn test_udp_sender_run_sender() {
         use std::thread;
@@ -164,12 +824,14 @@ mod tests {
 
             let mut buffer: [u8; 2048] = [0; 2048];
 
-            match socket.recv_from(&mut buffer) {
-                Ok((len, addr)) => {
-                    udp_listener_tx.send(str::from_utf8(&buffer[0..len]).unwrap().to_string()).unwrap();
-                }
+            for _ in 0..3 {
+                match socket.recv_from(&mut buffer) {
+                    Ok((len, addr)) => {
+                        udp_listener_tx.send(str::from_utf8(&buffer[0..len]).unwrap().to_string()).unwrap();
+                    }
 
-                Err(e) => panic!("Error occurred during receive udp datagram: {}", e)
+                    Err(e) => panic!("Error occurred during receive udp datagram: {}", e)
+                }
             }
         }));
 
@@ -179,15 +841,18 @@ mod tests {
         let (mut tx, mut rx) = channel();
 
         handles.push(thread::spawn(move || {
-            let mut sender = UdpSender::new("127.0.0.1:13345", simple_formatter, rx).unwrap();
+            let mut sender = UdpSender::new("127.0.0.1:13345", None, None, Box::new(TextFormatter::new(simple_formatter, FormatOptions::default())), rx).unwrap();
             sender.run_sender();
         }));
 
-        tx.send(data).unwrap();
-        tx.send(vec![(0_u32, 33_u8)]).unwrap();
-        let recv_data = udp_listener_rx.recv().unwrap();
+        let info = DumpInfo { sequence: 1, window_start: 0, timestamp: 1500000000, total: 3 };
+        tx.send(DumpEvent::Begin(info)).unwrap();
+        tx.send(DumpEvent::Chunk(0, data)).unwrap();
+        tx.send(DumpEvent::End(info)).unwrap();
 
-        assert_eq!("192.168.2.1/32 172.16.100.1/24 10.10.1.1/16".to_string(), recv_data);
+        assert_eq!("BEGIN 1 1500000000 3".to_string(), udp_listener_rx.recv().unwrap());
+        assert_eq!("192.168.2.1/32 172.16.100.1/24 10.10.1.1/16".to_string(), udp_listener_rx.recv().unwrap());
+        assert_eq!("END 1 1500000000 3".to_string(), udp_listener_rx.recv().unwrap());
 
         drop(tx);
         drop(udp_listener_rx);
@@ -197,4 +862,241 @@ mod tests {
             handle.join().unwrap();
         }
     }
+
+    #[test]
+    fn test_tcp_newline_framing_requires_single_line_formatter() {
+        let sink = |formatter: &str, framing: &str| {
+            senders(&format!("{{sender: tcp, formatter: {}, tcp: {{address: '127.0.0.1:13348', framing: {}}}}}", formatter, framing))
+        };
+        assert!(get_publisher_credentials(&sink("simple", "newline")).is_ok());
+        assert!(get_publisher_credentials(&sink("ipset", "newline")).is_err());
+        assert!(get_publisher_credentials(&sink("binary", "newline")).is_err());
+        assert!(get_publisher_credentials(&sink("ipset", "length_prefixed")).is_ok());
+    }
+
+    #[test]
+    fn test_tcp_publisher_reconnects() {
+        use std::io::BufReader;
+        use std::net::TcpListener;
+        use std::sync::mpsc::channel;
+        use std::thread;
+        use formatters::simple_formatter;
+        use self::tcp::{TcpPublisher, TcpPublisherOptions};
+
+        let (tx, rx) = channel();
+        let options = TcpPublisherOptions {
+            framing: Framing::LengthPrefixed,
+            buffer_dumps: 1,
+            min_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+        };
+        let handle = thread::spawn(move || {
+            TcpPublisher::new("127.0.0.1:13346".to_owned(), options, Box::new(TextFormatter::new(simple_formatter, FormatOptions::default())), rx).run_sender();
+        });
+
+        // nobody listens yet, only the newest dump is kept
+        for sequence in 1..3 {
+            let info = DumpInfo { sequence, window_start: 0, timestamp: 1500000000, total: 2 };
+            tx.send(DumpEvent::Begin(info)).unwrap();
+            tx.send(DumpEvent::Chunk(0, vec![(make_prefix([10, 0, 0, 0]), 8), (make_prefix([10, 1, 0, 0]), 16)])).unwrap();
+            tx.send(DumpEvent::End(info)).unwrap();
+        }
+        thread::sleep(Duration::from_millis(50));
+
+        let listener = TcpListener::bind("127.0.0.1:13346").unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut frames = Vec::new();
+        for _ in 0..3 {
+            frames.push(String::from_utf8(Framing::LengthPrefixed.read_frame(&mut reader).unwrap().unwrap()).unwrap());
+        }
+        assert_eq!(vec!["BEGIN 2 1500000000 2", "10.0.0.0/8 10.1.0.0/16", "END 2 1500000000 2"], frames);
+
+        drop(tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_file_publisher_rotation() {
+        use std::env::temp_dir;
+        use std::fs;
+        use std::io::Read;
+        use std::sync::mpsc::channel;
+        use flate2::read::GzDecoder;
+        use formatters::simple_formatter;
+        use self::file::FilePublisher;
+
+        let template = temp_dir().join("ipaggregator-test-file-{sequence}.gz").to_str().unwrap().to_owned();
+        let (tx, rx) = channel();
+        for sequence in 1..4 {
+            let info = DumpInfo { sequence, window_start: 0, timestamp: 1500000000, total: 2 };
+            tx.send(DumpEvent::Begin(info)).unwrap();
+            tx.send(DumpEvent::Chunk(0, vec![(make_prefix([10, 0, 0, 0]), 8), (make_prefix([10, 1, 0, 0]), 16)])).unwrap();
+            tx.send(DumpEvent::End(info)).unwrap();
+        }
+        drop(tx);
+        FilePublisher::new(template.clone(), 2, true, Box::new(TextFormatter::new(simple_formatter, FormatOptions::default())), rx).run_sender();
+
+        let path = |sequence: u32| template.replace("{sequence}", &sequence.to_string());
+        assert!(fs::metadata(path(1)).is_err());
+        assert!(fs::metadata(path(2)).is_ok());
+        let mut content = String::new();
+        GzDecoder::new(fs::File::open(path(3)).unwrap()).read_to_string(&mut content).unwrap();
+        assert_eq!("BEGIN 3 1500000000 2\n10.0.0.0/8 10.1.0.0/16\nEND 3 1500000000 2\n", content);
+        fs::remove_file(path(2)).unwrap();
+        fs::remove_file(path(3)).unwrap();
+    }
+
+    #[test]
+    fn test_file_publisher_rejects_keep_zero() {
+        let sink = |keep: usize| senders(&format!("{{sender: file, file: {{path: '/tmp/ranges-{{sequence}}.txt', keep: {}}}}}", keep));
+        assert!(get_publisher_credentials(&sink(0)).is_err());
+        assert!(get_publisher_credentials(&sink(1)).is_ok());
+    }
+
+    #[test]
+    fn test_newline_delimited_publishers_reject_binary() {
+        assert!(get_publisher_credentials(&senders("{sender: file, formatter: binary, file: {path: /tmp/ranges.bin}}")).is_err());
+        assert!(get_publisher_credentials(&senders("{sender: webhook, formatter: binary, webhook: {url: 'http://127.0.0.1:13349/', body: text}}")).is_err());
+        assert!(get_publisher_credentials(&senders("{sender: webhook, formatter: binary, webhook: {url: 'http://127.0.0.1:13349/', body: json}}")).is_ok());
+        assert!(get_publisher_credentials(&senders("{sender: udp, formatter: binary}")).is_ok());
+    }
+
+    #[test]
+    fn test_webhook_make_body() {
+        use std::collections::BTreeSet;
+        use self::webhook::{make_body, WebhookBody, WebhookMode};
+
+        let info = DumpInfo { sequence: 2, window_start: 0, timestamp: 1500000000, total: 2 };
+        let current: BTreeSet<(u32, u8)> = vec![(make_prefix([10, 0, 0, 0]), 8), (make_prefix([192, 168, 0, 0]), 16)].into_iter().collect();
+        let delivered: BTreeSet<(u32, u8)> = vec![(make_prefix([10, 0, 0, 0]), 8), (make_prefix([172, 16, 0, 0]), 12)].into_iter().collect();
+        let messages = vec![b"10.0.0.0/8 192.168.0.0/16".to_vec()];
+
+        assert_eq!(b"{\"sequence\":2,\"timestamp\":1500000000,\"total\":2,\"prefixes\":[\"10.0.0.0/8\",\"192.168.0.0/16\"]}".to_vec(),
+                   make_body(WebhookBody::Json, WebhookMode::Full, &info, &messages, &current, &delivered));
+        assert_eq!(b"{\"sequence\":2,\"timestamp\":1500000000,\"total\":2,\"added\":[\"192.168.0.0/16\"],\"removed\":[\"172.16.0.0/12\"]}".to_vec(),
+                   make_body(WebhookBody::Json, WebhookMode::Delta, &info, &messages, &current, &delivered));
+        assert_eq!(b"10.0.0.0/8 192.168.0.0/16\n".to_vec(),
+                   make_body(WebhookBody::Text, WebhookMode::Full, &info, &messages, &current, &delivered));
+        assert_eq!(b"+192.168.0.0/16\n-172.16.0.0/12\n".to_vec(),
+                   make_body(WebhookBody::Text, WebhookMode::Delta, &info, &messages, &current, &delivered));
+    }
+
+    #[test]
+    fn test_webhook_publisher_retries() {
+        use std::env::temp_dir;
+        use std::fs;
+        use std::io::BufReader;
+        use std::net::TcpListener;
+        use std::sync::mpsc::channel;
+        use std::thread;
+        use formatters::simple_formatter;
+        use http::{read_request, write_response};
+        use self::webhook::{WebhookBody, WebhookMode, WebhookOptions, WebhookPublisher};
+
+        let listener = TcpListener::bind("127.0.0.1:13347").unwrap();
+        let server = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            // first dump fails once, second dump fails for good
+            for status in &[500, 202, 500, 500] {
+                let (stream, _) = listener.accept().unwrap();
+                let request = read_request(&mut BufReader::new(stream.try_clone().unwrap())).unwrap().unwrap();
+                assert_eq!(Some("secret"), request.header("X-Token"));
+                bodies.push(String::from_utf8(request.body).unwrap());
+                write_response(&mut &stream, *status, "").unwrap();
+            }
+            bodies
+        });
+
+        let dead_letter = temp_dir().join("ipaggregator-test-webhook-dead-letter");
+        let _ = fs::remove_file(&dead_letter);
+        let options = WebhookOptions {
+            headers: vec![("X-Token".to_owned(), "secret".to_owned())],
+            body: WebhookBody::Text,
+            mode: WebhookMode::Delta,
+            retries: 1,
+            retry_backoff: Duration::from_millis(10),
+            timeout: Duration::from_secs(5),
+            dead_letter: Some(dead_letter.to_str().unwrap().to_owned()),
+        };
+        let (tx, rx) = channel();
+        for (sequence, octet) in vec![(1, 10), (2, 11)] {
+            let info = DumpInfo { sequence, window_start: 0, timestamp: 1500000000, total: 1 };
+            tx.send(DumpEvent::Begin(info)).unwrap();
+            tx.send(DumpEvent::Chunk(0, vec![(make_prefix([octet, 0, 0, 0]), 8)])).unwrap();
+            tx.send(DumpEvent::End(info)).unwrap();
+        }
+        drop(tx);
+        WebhookPublisher::new(parse_url("http://127.0.0.1:13347/hook").unwrap(), options,
+                              Box::new(TextFormatter::new(simple_formatter, FormatOptions::default())), rx).run_sender();
+
+        assert_eq!(vec!["+10.0.0.0/8\n", "+10.0.0.0/8\n", "+11.0.0.0/8\n-10.0.0.0/8\n", "+11.0.0.0/8\n-10.0.0.0/8\n"],
+                   server.join().unwrap());
+        assert_eq!("+11.0.0.0/8\n-10.0.0.0/8\n\n", fs::read_to_string(&dead_letter).unwrap());
+        fs::remove_file(&dead_letter).unwrap();
+    }
+
+    #[test]
+    fn test_kafka_range_formatter_keys() {
+        use formatters::RangeFormatter;
+        use self::kafka::{keyed_messages, MessageKey};
+
+        let sink = |key: &str| {
+            senders(&format!("{{sender: kafka, formatter: range, kafka: {{hosts: ['127.0.0.1:9092'], topic: ranges, message_key: {}}}}}", key))
+        };
+        assert!(get_publisher_credentials(&sink("none")).is_ok());
+        assert!(get_publisher_credentials(&sink("first_octet")).is_err());
+        assert!(get_publisher_credentials(&sink("prefix")).is_err());
+
+        assert!(get_publisher_credentials(&senders("{sender: kafka, formatter: json, kafka: {hosts: ['127.0.0.1:9092'], topic: ranges}}")).is_err());
+        assert!(get_publisher_credentials(&senders("{sender: udp, formatter: json}")).is_err());
+
+        // unkeyed records keep ranges spanning chunks whole
+        let mut formatter = RangeFormatter::new(FormatOptions::default());
+        let info = DumpInfo { sequence: 1, window_start: 0, timestamp: 0, total: 3 };
+        let mut values = Vec::new();
+        for event in vec![DumpEvent::Begin(info),
+                          DumpEvent::Chunk(0, vec![(make_prefix([10, 0, 0, 0]), 24), (make_prefix([10, 0, 1, 0]), 24)]),
+                          DumpEvent::Chunk(2, vec![(make_prefix([10, 0, 2, 0]), 24)]),
+                          DumpEvent::End(info)] {
+            values.extend(keyed_messages(MessageKey::None, &mut formatter, &event).into_iter()
+                .map(|(key, value)| (key, String::from_utf8(value).unwrap())));
+        }
+        assert_eq!(vec![("".to_owned(), "10.0.0.0-10.0.2.255".to_owned())], values);
+    }
+
+    fn joining_formatter(event: &DumpEvent, _: &FormatOptions) -> Vec<String> {
+        match *event {
+            DumpEvent::Chunk(_, ref cidrs) => vec![cidrs.iter().map(make_cidr_ip_string).collect::<Vec<String>>().join(" ")],
+            _ => vec!["marker".to_owned()]
+        }
+    }
+
+    #[test]
+    fn test_kafka_keyed_messages() {
+        use self::kafka::{keyed_messages, MessageKey};
+
+        let data = vec![
+            (make_prefix([10, 0, 0, 0]), 24),
+            (make_prefix([192, 168, 0, 0]), 16),
+            (make_prefix([10, 1, 0, 0]), 16)];
+        let mut joining = TextFormatter::new(joining_formatter, FormatOptions::default());
+        let mut keyed = |key: MessageKey, event: DumpEvent| -> Vec<(String, String)> {
+            keyed_messages(key, &mut joining, &event).into_iter()
+                .map(|(key, value)| (key, String::from_utf8(value).unwrap())).collect()
+        };
+
+        assert_eq!(vec![("".to_owned(), "10.0.0.0/24 192.168.0.0/16 10.1.0.0/16".to_owned())],
+                   keyed(MessageKey::None, DumpEvent::Chunk(0, data.clone())));
+        assert_eq!(vec![("10".to_owned(), "10.0.0.0/24 10.1.0.0/16".to_owned()),
+                        ("192".to_owned(), "192.168.0.0/16".to_owned())],
+                   keyed(MessageKey::FirstOctet, DumpEvent::Chunk(0, data.clone())));
+        assert_eq!(vec![("10.0.0.0/24".to_owned(), "10.0.0.0/24".to_owned()),
+                        ("192.168.0.0/16".to_owned(), "192.168.0.0/16".to_owned()),
+                        ("10.1.0.0/16".to_owned(), "10.1.0.0/16".to_owned())],
+                   keyed(MessageKey::Prefix, DumpEvent::Chunk(0, data)));
+        let info = DumpInfo { sequence: 1, window_start: 0, timestamp: 0, total: 3 };
+        assert_eq!(vec![("".to_owned(), "marker".to_owned())],
+                   keyed(MessageKey::Prefix, DumpEvent::End(info)));
+    }
 }
@@ -0,0 +1,56 @@
+use libc;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+static RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_shutdown(_signal: libc::c_int) {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn request_reload(_signal: libc::c_int) {
+    RELOAD_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Installs SIGTERM and SIGINT handlers requesting shutdown and SIGHUP handler requesting
+/// config reload. Handlers only raise a flag, which is polled by the main thread.
+pub fn install_handlers() -> Result<(), String> {
+    let shutdown = request_shutdown as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    let reload = request_reload as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    for &(signal, handler) in &[(libc::SIGTERM, shutdown), (libc::SIGINT, shutdown), (libc::SIGHUP, reload)] {
+        if unsafe { libc::signal(signal, handler) } == libc::SIG_ERR {
+            return Err(format!("Can not install handler of signal {}; Cause: {}", signal, io::Error::last_os_error()));
+        }
+    }
+    Ok(())
+}
+
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Returns whether reload was requested since the last call.
+pub fn reload_requested() -> bool {
+    RELOAD_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sigterm_requests_shutdown() {
+        install_handlers().unwrap();
+        unsafe { libc::raise(libc::SIGTERM) };
+        assert!(shutdown_requested());
+    }
+
+    #[test]
+    fn test_sighup_requests_reload_once() {
+        install_handlers().unwrap();
+        unsafe { libc::raise(libc::SIGHUP) };
+        assert!(reload_requested());
+        assert!(!reload_requested());
+    }
+}
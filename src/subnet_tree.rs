@@ -1,8 +1,24 @@
-use SETTINGS;
 use std::collections::{HashMap, LinkedList};
 use std::iter::Iterator;
 use std::ops::BitXor;
 
+/// Aggregation behaviour of the tree.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct TreeOptions {
+    /// Every /24 gets its `x.y.z.0` address added once any of its addresses is seen.
+    pub add_zeroed: bool,
+    /// Every /24 gets its `x.y.z.255` address added once any of its addresses is seen.
+    pub add_broadcast: bool,
+}
+
+impl Default for TreeOptions {
+    fn default() -> TreeOptions {
+        TreeOptions {
+            add_zeroed: true,
+            add_broadcast: true,
+        }
+    }
+}
 
 pub trait OctetNode: Send {
     fn add(&mut self, octets: &[u8]) -> ();
@@ -21,17 +37,23 @@ pub struct StandardNode {
     level: u8,
     heap: [u64; 8],
     subnodes: HashMap<u8, Box<OctetNode>>,
+    options: TreeOptions,
 }
 
 // TODO create better names
 // > @pastMe: Nah I'm too lazy
 impl StandardNode {
     pub fn new(octet: u8, level: u8) -> StandardNode {
+        Self::with_options(octet, level, TreeOptions::default())
+    }
+
+    pub fn with_options(octet: u8, level: u8, options: TreeOptions) -> StandardNode {
         StandardNode {
             octet,
             level,
             heap: [0; 8],
             subnodes: HashMap::new(),
+            options,
         }
     }
 
@@ -99,20 +121,20 @@ impl StandardNode {
             Some(_) => {}
             None => {
                 if self.level == 0 {
-                    if SETTINGS.is_last_node_with_settings() {
+                    if self.options.add_zeroed || self.options.add_broadcast {
                         self.subnodes.insert(octet,
                                              Box::new(
                                                  LastNode::new_with_opts(
                                                      octet,
-                                                     SETTINGS.add_zeroed(),
-                                                     SETTINGS.add_broadcast(),
+                                                     self.options.add_zeroed,
+                                                     self.options.add_broadcast,
                                                  )
                                              ));
                     } else {
                         self.subnodes.insert(octet, Box::new(LastNode::new(octet)));
                     }
                 } else {
-                    self.subnodes.insert(octet, Box::new(StandardNode::new(octet, self.level - 1)));
+                    self.subnodes.insert(octet, Box::new(StandardNode::with_options(octet, self.level - 1, self.options)));
                 }
             }
         }
@@ -399,13 +421,19 @@ impl Neighboring for u16 {
 }
 
 pub struct IPTree {
-    octets: HashMap<u8, Box<OctetNode>>
+    octets: HashMap<u8, Box<OctetNode>>,
+    options: TreeOptions,
 }
 
 impl IPTree {
     pub fn new() -> IPTree {
+        Self::with_options(TreeOptions::default())
+    }
+
+    pub fn with_options(options: TreeOptions) -> IPTree {
         IPTree {
-            octets: HashMap::new()
+            octets: HashMap::new(),
+            options,
         }
     }
 
@@ -413,7 +441,7 @@ impl IPTree {
         if self.octets.contains_key(&octet) {
             return;
         }
-        self.octets.insert(octet, Box::new(StandardNode::new(octet, 1)));
+        self.octets.insert(octet, Box::new(StandardNode::with_options(octet, 1, self.options)));
     }
 
     fn clear(&mut self) -> () {
@@ -627,6 +655,7 @@ mod tests {
             heap: [2, 0, 0, 0, 0, 0, 0, 0],
             subnodes: HashMap::new(),
             octet: 0,
+            options: TreeOptions::default(),
         };
         assert!(node.contains_subnet_in_heap(1));
         assert!(!node.contains_subnet_in_heap(2));
@@ -639,6 +668,7 @@ mod tests {
             heap: [1, 0, 0, 0, 0, 0, 0, 0],
             subnodes: HashMap::new(),
             level: 0,
+            options: TreeOptions::default(),
         };
         node.unset_heap_bit(0);
         assert_eq!(node.heap, [0; 8]);
@@ -651,6 +681,7 @@ mod tests {
             heap: [0, 0, 0, 0, 0, 0, 0, 0],
             subnodes: HashMap::new(),
             level: 0,
+            options: TreeOptions::default(),
         };
         node.set_heap_bit(0);
         assert_eq!(node.heap, [1, 0, 0, 0, 0, 0, 0, 0]);
@@ -663,6 +694,7 @@ mod tests {
             heap: [0, 0, 0, 0, 3, 0, 0, 0],
             subnodes: HashMap::new(),
             level: 0,
+            options: TreeOptions::default(),
         };
         node.merge_subnets(256 + 1);
         assert_eq!(node.heap, [0, 0, 1, 0, 0, 0, 0, 0]);
@@ -676,6 +708,7 @@ mod tests {
             heap: [0, 0, 0, 0, 1, 0, 0, 0],
             subnodes: HashMap::new(),
             level: 0,
+            options: TreeOptions::default(),
         };
         assert!(node.is_part_of_aggregated_subnet(0));
         assert!(!node.is_part_of_aggregated_subnet(1));
@@ -686,6 +719,7 @@ mod tests {
             heap: [0, 0, 0, 0, 1, 0, 0, 0],
             subnodes: HashMap::new(),
             level: 0,
+            options: TreeOptions::default(),
         };
         node.subnodes.insert(0, Box::new(LastNode {
             heap: [2, 0, 0, 0, 0, 0, 0, 0],
@@ -699,6 +733,7 @@ mod tests {
             heap: [0, 0, 0, 0, 1, 0, 0, 0],
             subnodes: HashMap::new(),
             level: 0,
+            options: TreeOptions::default(),
         };
         node.subnodes.insert(0, Box::new(LastNode {
             heap: [0, 0, 0, 0, 0, 0, 0, 0],
@@ -712,6 +747,7 @@ mod tests {
             heap: [0, 0, 0, 0, 0, 0, 0, 0],
             subnodes: HashMap::new(),
             level: 0,
+            options: TreeOptions::default(),
         };
         node.subnodes.insert(0, Box::new(LastNode {
             heap: [0, 0, 0, 0, 1, 0, 0, 0],
@@ -725,6 +761,7 @@ mod tests {
             heap: [2, 0, 0, 0, 0, 0, 0, 0],
             subnodes: HashMap::new(),
             level: 0,
+            options: TreeOptions::default(),
         };
         assert!(node.is_part_of_aggregated_subnet(23));
     }
@@ -744,6 +781,7 @@ mod tests {
             level: 0,
             heap: [2, 0, 0, 0, 0, 0, 0, 0],
             subnodes: HashMap::new(),
+            options: TreeOptions::default(),
         };
         assert!(!node.subnodes.contains_key(&1));
         node.expand(1);
@@ -767,6 +805,7 @@ mod tests {
             level: 0,
             subnodes: HashMap::new(),
             heap: [2, 0, 0, 0, 0, 0, 0, 0],
+            options: TreeOptions::default(),
         };
         assert!(node.contains(&2));
     }
@@ -798,6 +837,7 @@ mod tests {
             level: 0,
             subnodes: HashMap::new(),
             heap: [2, 0, 0, 0, 0, 0, 0, 0],
+            options: TreeOptions::default(),
         };
         assert!(node.is_subnet());
 
@@ -853,6 +893,7 @@ mod tests {
             level: 0,
             heap: [0; 8],
             subnodes: HashMap::new(),
+            options: TreeOptions::default(),
         };
         let mut iter = node.walk(0, 0);
         assert_eq!(None, iter.next());
@@ -865,6 +906,7 @@ mod tests {
             level: 0, // No love for level
             heap: [0, 0, 0, 0, 2, 0, 0, 0],
             subnodes: HashMap::new(),
+            options: TreeOptions::default(),
         };
         let mut iter = node.walk(make_prefix([192, 0, 0, 0]), 8);
         assert_eq!(Some((make_prefix([192, 168, 1, 0]), 24)), iter.next());
@@ -878,6 +920,7 @@ mod tests {
             level: 0, // No love for level
             heap: [0, 0, 0, 0, 2 | 8 | 32, 0, 0, 0],
             subnodes: HashMap::new(),
+            options: TreeOptions::default(),
         };
         let mut iter = node.walk(make_prefix([192, 0, 0, 0]), 8);
         assert_eq!(Some((make_prefix([192, 168, 1, 0]), 24)), iter.next());
@@ -901,12 +944,30 @@ mod tests {
             level: 0, // No love for level
             heap: [0; 8],
             subnodes,
+            options: TreeOptions::default(),
         };
         let mut iter = node.walk(make_prefix([192, 0, 0, 0]), 8);
         assert_eq!(Some((make_prefix([192, 168, 1, 1]), 32)), iter.next());
         assert_eq!(None, iter.next());
     }
 
+    #[test]
+    fn test_tree_options() {
+        let mut tree = IPTree::with_options(TreeOptions { add_zeroed: false, add_broadcast: false });
+        tree.add(&[10, 0, 0, 1]);
+        assert_eq!(vec![(make_prefix([10, 0, 0, 1]), 32)], tree.walk().collect::<Vec<_>>());
+
+        let mut tree = IPTree::with_options(TreeOptions { add_zeroed: true, add_broadcast: false });
+        tree.add(&[10, 0, 0, 1]);
+        assert_eq!(vec![(make_prefix([10, 0, 0, 0]), 31)], tree.walk().collect::<Vec<_>>());
+
+        let mut tree = IPTree::new();
+        tree.add(&[10, 0, 0, 1]);
+        let mut cidrs: Vec<(u32, u8)> = tree.walk().collect();
+        cidrs.sort();
+        assert_eq!(vec![(make_prefix([10, 0, 0, 0]), 31), (make_prefix([10, 0, 0, 255]), 32)], cidrs);
+    }
+
     #[test]
     fn test_floor_log2() {
         assert_eq!(floor_log2(2), Ok(1));